@@ -0,0 +1,310 @@
+//! Runs a ROM without a display, for CI and batch jobs.
+//!
+//! Exits with 0 once the stop condition is met (or after `--frames` without one), 1 if the ROM
+//! failed or ran out of frames first, and 2 on bad arguments or emulation errors.
+
+use anyhow::{anyhow, Result};
+use gb::Color::{Black, DarkGray, LightGray, White};
+use gb::{Filter, GameBoy, Image, Movie, Palette, CYCLES_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+const USAGE: &str = "\
+usage: gb-headless <rom> [options]
+
+  --frames <n>               frames to run before giving up (default 3600)
+  --until-serial <text>      pass once the serial output contains text
+  --fail-serial <text>       fail once the serial output contains text
+  --until-pc <addr>          pass once PC reaches addr (hex)
+  --strict                   treat illegal opcodes as errors
+  --dump-framebuffer <file>  write the final screen as a PGM image
+  --screenshot <file>        write the final screen as a PNG in the default palette
+  --filter <name>            filter the screenshot: nearest, scale2x, scale3x or lcd
+  --scanlines                add scanlines to the screenshot
+  --record <file>            record every frame as a .y4m video in the default palette
+  --play-movie <file>        replay an input movie, passing once it ends unless another
+                             stop condition is given
+  --verify-movie             fail with an error if the screen differs from the movie's
+  --record-movie <file>      write the (empty) input of the run as a movie, to verify later
+  --dump-serial <file>       write the serial output
+  --dump-registers           print the final registers";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    rom: PathBuf,
+    frames: u64,
+    until_serial: Option<String>,
+    fail_serial: Option<String>,
+    until_pc: Option<u16>,
+    strict: bool,
+    dump_framebuffer: Option<PathBuf>,
+    screenshot: Option<PathBuf>,
+    filter: Filter,
+    scanlines: bool,
+    record: Option<PathBuf>,
+    play_movie: Option<PathBuf>,
+    verify_movie: bool,
+    record_movie: Option<PathBuf>,
+    dump_serial: Option<PathBuf>,
+    dump_registers: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Passed,
+    Failed,
+    TimedOut,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options> {
+    let mut args = args.into_iter();
+    let mut rom = None;
+    let mut options = Options {
+        rom: PathBuf::new(),
+        frames: 3600,
+        until_serial: None,
+        fail_serial: None,
+        until_pc: None,
+        strict: false,
+        dump_framebuffer: None,
+        screenshot: None,
+        filter: Filter::Nearest,
+        scanlines: false,
+        record: None,
+        play_movie: None,
+        verify_movie: false,
+        record_movie: None,
+        dump_serial: None,
+        dump_registers: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("{} needs a value", arg));
+        match arg.as_str() {
+            "--frames" => options.frames = value()?.parse()?,
+            "--until-serial" => options.until_serial = Some(value()?),
+            "--fail-serial" => options.fail_serial = Some(value()?),
+            "--until-pc" => {
+                let addr = value()?;
+                let digits = addr.trim_start_matches('$').trim_start_matches("0x");
+                options.until_pc = Some(
+                    u16::from_str_radix(digits, 16)
+                        .map_err(|_| anyhow!("Invalid address {}", addr))?,
+                );
+            }
+            "--strict" => options.strict = true,
+            "--dump-framebuffer" => options.dump_framebuffer = Some(value()?.into()),
+            "--screenshot" => options.screenshot = Some(value()?.into()),
+            "--filter" => {
+                let name = value()?;
+                options.filter =
+                    Filter::by_name(&name).ok_or_else(|| anyhow!("Unknown filter {}", name))?;
+            }
+            "--scanlines" => options.scanlines = true,
+            "--record" => options.record = Some(value()?.into()),
+            "--play-movie" => options.play_movie = Some(value()?.into()),
+            "--verify-movie" => options.verify_movie = true,
+            "--record-movie" => options.record_movie = Some(value()?.into()),
+            "--dump-serial" => options.dump_serial = Some(value()?.into()),
+            "--dump-registers" => options.dump_registers = true,
+            _ if arg.starts_with("--") => return Err(anyhow!("Unknown option {}", arg)),
+            _ if rom.is_none() => rom = Some(PathBuf::from(arg)),
+            _ => return Err(anyhow!("Unexpected argument {}", arg)),
+        }
+    }
+    options.rom = rom.ok_or_else(|| anyhow!("Missing ROM path"))?;
+    if options.verify_movie && options.play_movie.is_none() {
+        return Err(anyhow!("--verify-movie needs --play-movie"));
+    }
+    if options.play_movie.is_some() && options.record_movie.is_some() {
+        return Err(anyhow!("--play-movie and --record-movie can't be combined"));
+    }
+    Ok(options)
+}
+
+fn run(options: &Options) -> Result<(Outcome, GameBoy, String)> {
+    let mut gb = GameBoy::new(&options.rom)?;
+    gb.set_strict(options.strict);
+    if let Some(path) = &options.record {
+        gb.start_recording(path, &Palette::default())?;
+    }
+    if let Some(path) = &options.play_movie {
+        gb.play_movie(Movie::load(path)?, options.verify_movie)?;
+    }
+    if options.record_movie.is_some() {
+        gb.start_movie_recording()?;
+    }
+    let has_condition = options.until_serial.is_some() || options.until_pc.is_some();
+    let mut serial = String::new();
+
+    // Counted in cycles rather than `gb.frames()` so that ROMs which leave the LCD off still stop.
+    let max_cycles = options.frames as usize * CYCLES_PER_FRAME;
+    let outcome = loop {
+        if gb.cycles() >= max_cycles {
+            break Outcome::TimedOut;
+        }
+        if options.until_pc == Some(gb.registers().pc) {
+            break Outcome::Passed;
+        }
+        if options.play_movie.is_some() && !has_condition && !gb.is_playing_movie() {
+            break Outcome::Passed;
+        }
+        if let (Some(log), _) = gb.step()? {
+            serial.push_str(&log);
+            if options
+                .fail_serial
+                .as_ref()
+                .is_some_and(|text| serial.contains(text.as_str()))
+            {
+                break Outcome::Failed;
+            }
+            if options
+                .until_serial
+                .as_ref()
+                .is_some_and(|text| serial.contains(text.as_str()))
+            {
+                break Outcome::Passed;
+            }
+        }
+    };
+
+    let outcome = match outcome {
+        Outcome::TimedOut if !has_condition => Outcome::Passed,
+        outcome => outcome,
+    };
+    Ok((outcome, gb, serial))
+}
+
+/// Binary PGM, which most image tools read, with the four shades spread over 0-255.
+fn write_pgm(gb: &GameBoy, path: &Path) -> Result<()> {
+    let mut pgm = format!("P5\n{} {}\n255\n", SCREEN_WIDTH, SCREEN_HEIGHT).into_bytes();
+    pgm.extend(gb.frame_buffer().pixels().iter().map(|color| match color {
+        White => 255,
+        LightGray => 170,
+        DarkGray => 85,
+        Black => 0,
+    }));
+    fs::write(path, pgm)?;
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("gb-headless: {}\n\n{}", e, USAGE);
+            exit(2)
+        }
+    };
+    match run_and_dump(&options) {
+        Ok(Outcome::Passed) => exit(0),
+        Ok(_) => exit(1),
+        Err(e) => {
+            eprintln!("gb-headless: {}", e);
+            exit(2)
+        }
+    }
+}
+
+fn run_and_dump(options: &Options) -> Result<Outcome> {
+    let (outcome, mut gb, serial) = run(options)?;
+    gb.stop_recording()?;
+    if let (Some(path), Some(movie)) = (&options.record_movie, gb.stop_movie()) {
+        movie.save(path)?;
+    }
+    if let Some(path) = &options.dump_framebuffer {
+        write_pgm(&gb, path)?;
+    }
+    if let Some(path) = &options.screenshot {
+        Image::filtered(
+            gb.frame_buffer(),
+            &Palette::default(),
+            options.filter,
+            options.scanlines,
+        )
+        .save_png(path)?;
+    }
+    if let Some(path) = &options.dump_serial {
+        fs::write(path, &serial)?;
+    }
+    if options.dump_registers {
+        println!("{}", gb.registers());
+    }
+    println!("{:?} after {} frames", outcome, gb.frames());
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_args, run, run_and_dump, Outcome};
+    use gb::CYCLES_PER_FRAME;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_until_serial() -> anyhow::Result<()> {
+        let dir = TempDir::new("boyohboy")?;
+        let path = dir.path().join("serial.gb");
+        let mut rom = vec![0u8; 0x8000];
+        // Send "OK" over serial, then loop forever.
+        rom[0x0100..0x0112].copy_from_slice(&[
+            0x3E, b'O', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, //
+            0x3E, b'K', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, //
+            0x18, 0xFE,
+        ]);
+        fs::write(&path, rom)?;
+        let rom = path.display().to_string();
+
+        let options = parse_args([rom.clone(), "--until-serial".into(), "OK".into()])?;
+        let (outcome, _, serial) = run(&options)?;
+        assert_eq!((outcome, serial.as_str()), (Outcome::Passed, "OK"));
+
+        let options = parse_args([rom.clone(), "--until-pc".into(), "$0110".into()])?;
+        let (outcome, gb, _) = run(&options)?;
+        assert_eq!((outcome, gb.registers().pc), (Outcome::Passed, 0x0110));
+
+        let options = parse_args([
+            rom,
+            "--until-serial".into(),
+            "Passed".into(),
+            "--frames".into(),
+            "2".into(),
+        ])?;
+        let (outcome, _, _) = run(&options)?;
+        assert_eq!(outcome, Outcome::TimedOut);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_recorded_movie() -> anyhow::Result<()> {
+        let dir = TempDir::new("boyohboy")?;
+        let path = dir.path().join("loop.gb");
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x0100..0x0102].copy_from_slice(&[0x18, 0xFE]);
+        fs::write(&path, rom)?;
+        let rom = path.display().to_string();
+        let movie = dir.path().join("loop.movie").display().to_string();
+
+        let options = parse_args([
+            rom.clone(),
+            "--frames".into(),
+            "3".into(),
+            "--record-movie".into(),
+            movie.clone(),
+        ])?;
+        run_and_dump(&options)?;
+        let options = parse_args([rom, "--play-movie".into(), movie, "--verify-movie".into()])?;
+        let (outcome, gb, _) = run(&options)?;
+        assert_eq!(outcome, Outcome::Passed);
+        assert_eq!(gb.cycles() / CYCLES_PER_FRAME, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_arguments() {
+        assert!(parse_args(["--frames".to_string()]).is_err());
+        assert!(parse_args(["--bogus".to_string()]).is_err());
+        assert!(parse_args(Vec::<String>::new()).is_err());
+        assert!(parse_args(["a.gb".to_string(), "--verify-movie".to_string()]).is_err());
+    }
+}
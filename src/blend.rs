@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Result};
+use gb::{FrameBuffer, Image, Palette, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::collections::VecDeque;
+
+/// Mixes the last few frames like the DMG's slow LCD does, for games that flicker sprites on
+/// alternate frames to fake transparency. Only what is shown is blended; the emulated frames are
+/// left alone.
+pub struct FrameBlender {
+    /// Weight of the newest frame first, summing to 1.
+    weights: Vec<f32>,
+    frames: VecDeque<FrameBuffer>,
+}
+
+impl FrameBlender {
+    pub fn new(weights: &[f32]) -> FrameBlender {
+        let total: f32 = weights.iter().sum();
+        FrameBlender {
+            weights: weights.iter().map(|weight| weight / total).collect(),
+            frames: VecDeque::with_capacity(weights.len()),
+        }
+    }
+
+    pub fn push(&mut self, frame_buffer: &FrameBuffer) {
+        if self.frames.len() == self.weights.len() {
+            self.frames.pop_back();
+        }
+        self.frames.push_front(frame_buffer.clone());
+    }
+
+    /// The weighted mix of the frames pushed so far, in `palette`.
+    pub fn blend(&self, palette: &Palette) -> Image {
+        if self.frames.is_empty() {
+            return Image::new(&FrameBuffer::new(), palette);
+        }
+        let mut mix = vec![0f32; SCREEN_WIDTH * SCREEN_HEIGHT * 3];
+        // Until enough frames have been seen, the weights of the missing ones go to the oldest.
+        let frames = (0..self.weights.len()).map(|i| &self.frames[i.min(self.frames.len() - 1)]);
+        for (frame, weight) in frames.zip(&self.weights) {
+            let rgb = frame.to_rgb(palette, 1);
+            for (mixed, c) in mix.iter_mut().zip(rgb) {
+                *mixed += f32::from(c) * weight;
+            }
+        }
+        Image {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            rgb: mix.into_iter().map(|c| c.round() as u8).collect(),
+        }
+    }
+}
+
+/// Comma separated weights, newest frame first, e.g. `1,1` for an even mix with the previous one.
+pub fn parse_weights(text: &str) -> Result<Vec<f32>> {
+    let weights = text
+        .split(',')
+        .map(|weight| weight.trim().parse::<f32>().ok().filter(|w| *w >= 0.0))
+        .collect::<Option<Vec<f32>>>()
+        .filter(|weights| weights.iter().sum::<f32>() > 0.0)
+        .ok_or_else(|| anyhow!("Invalid blend weights {}", text))?;
+    Ok(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_weights, FrameBlender};
+    use gb::{Color, FrameBuffer, Layer, Palette, Pixel};
+
+    #[test]
+    fn test_blend_with_previous_frame() -> anyhow::Result<()> {
+        let mut blender = FrameBlender::new(&parse_weights("1, 1")?);
+        let white = FrameBuffer::new();
+        let mut black = FrameBuffer::new();
+        black.draw(&Pixel {
+            x: 0,
+            y: 0,
+            color: Color::Black,
+            layer: Layer::Object0,
+        });
+
+        blender.push(&black);
+        assert_eq!(blender.blend(&Palette::default()).rgb[..3], [0, 0, 0]);
+        blender.push(&white);
+        assert_eq!(
+            blender.blend(&Palette::default()).rgb[..6],
+            [128, 128, 128, 255, 255, 255]
+        );
+        blender.push(&white);
+        assert_eq!(blender.blend(&Palette::default()).rgb[..3], [255, 255, 255]);
+
+        assert!(parse_weights("1,x").is_err());
+        assert!(parse_weights("0,0").is_err());
+        Ok(())
+    }
+}
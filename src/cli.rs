@@ -0,0 +1,316 @@
+use crate::blend::parse_weights;
+use crate::input::Bindings;
+use crate::presenter::Scaling;
+use anyhow::{anyhow, Result};
+use gb::{Filter, Palette, FILTERS, PALETTE_PRESETS};
+use itertools::Itertools;
+use log::LevelFilter;
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+usage: gb <rom> [options]
+
+  --scale <n>            initial window scale factor (default 3), = and - change it
+  --scaling <mode>       integer, fit (square pixels) or stretch (default integer), F4
+                         cycles through them
+  --fullscreen           start fullscreen, F11 toggles it
+  --filter <name>        nearest, scale2x, scale3x or lcd (default nearest), F5 cycles
+                         through them
+  --scanlines            darken every other line, F6 toggles them
+  --blend <weights>      mix in previous frames like the DMG's slow LCD, newest first, e.g.
+                         1,1 (default when F7 turns blending on)
+  --palette <name>       color palette: gray, dmg, pocket, light, high-contrast, cgb or one
+                         from --palette-file (default gray), F3 cycles through them
+  --palette-file <file>  custom palettes, one [name] section each with bg, obj0 and obj1
+                         lines of four RRGGBB shades
+  --keys <file>          key bindings, one button = key, key... line each, e.g. a = X or
+                         turbo_b = A, using SDL key names; buttons are right, left, up,
+                         down, a, b, select, start, turbo_a and turbo_b (default arrows,
+                         X/Z for A/B, Return for Start, Backspace for Select, S/A turbo)
+  --boot-rom <file>      run a 256 byte DMG boot ROM first
+  --save-dir <dir>       where battery saves are kept (default: next to the ROM)
+  --capture-dir <dir>    where F12 (native) and Shift+F12 (as shown) screenshots and F10
+                         recordings go (default: next to the ROM; --screenshot-dir is an
+                         older name for it)
+  --record               start recording video right away
+  --cheats <file>        Game Genie (ABC-DEF-GHI) and GameShark (TTVVAAAA) codes, one cheat
+                         per line with an optional description, ! to start it disabled
+                         (default: game.cht next to the ROM), F8 toggles them all
+  --record-movie <file>  record the input of the run from power on as a movie, saved on exit
+  --play-movie <file>    replay a movie, after which the keys take over again
+  --verify-movie         stop with an error if the screen differs from the movie's
+  --log-level <level>    off, error, warn, info, debug or trace (default warn)
+  --paused               start paused, P toggles pause and N advances a frame while paused
+  --fast-forward <n>     speed while Tab is held (default 4, 0 for as fast as possible)
+  --slow-motion <n>      speed while ` is held (default 0.5)
+  --show-fps             show frame rate and speed, F9 toggles them
+  --strict               stop on illegal opcodes instead of locking up
+  --trace <file>         write a gameboy-doctor trace
+  --trace-ly-stub        make LY read 0x90 while tracing
+  --trace-symbols        append .sym labels to trace lines
+  --debug                start in the debugger REPL
+  --gdb <port>           wait for GDB on localhost:port
+  --profile <prefix>     write <prefix>.folded and <prefix>.txt on exit
+  --cdl <file>           accumulate a code/data log in file, in BizHawk's CDL format
+  --help                 show this message";
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub rom: PathBuf,
+    pub scale: u32,
+    pub scaling: Scaling,
+    pub fullscreen: bool,
+    pub filter: Filter,
+    pub scanlines: bool,
+    /// Frame blending weights, newest first, if blending starts on.
+    pub blend: Option<Vec<f32>>,
+    /// The presets followed by any custom palettes.
+    pub palettes: Vec<(String, Palette)>,
+    /// Index of the palette to start with.
+    pub palette: usize,
+    pub bindings: Bindings,
+    pub boot_rom: Option<PathBuf>,
+    pub save_dir: Option<PathBuf>,
+    pub capture_dir: Option<PathBuf>,
+    pub record: bool,
+    pub cheats: Option<PathBuf>,
+    pub record_movie: Option<PathBuf>,
+    pub play_movie: Option<PathBuf>,
+    pub verify_movie: bool,
+    pub log_level: LevelFilter,
+    pub paused: bool,
+    pub fast_forward: f64,
+    pub slow_motion: f64,
+    pub show_fps: bool,
+    pub strict: bool,
+    pub trace: Option<PathBuf>,
+    pub trace_ly_stub: bool,
+    pub trace_symbols: bool,
+    pub debug: bool,
+    pub gdb: Option<u16>,
+    pub profile: Option<String>,
+    pub cdl: Option<PathBuf>,
+}
+
+/// Returns `None` for `--help`.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>> {
+    let mut args = args.into_iter();
+    let mut rom = None;
+    let mut palette = None;
+    let mut options = Options {
+        rom: PathBuf::new(),
+        scale: 3,
+        scaling: Scaling::Integer,
+        fullscreen: false,
+        filter: Filter::Nearest,
+        scanlines: false,
+        blend: None,
+        palettes: PALETTE_PRESETS
+            .iter()
+            .map(|(name, palette)| (name.to_string(), *palette))
+            .collect(),
+        palette: 0,
+        bindings: Bindings::default(),
+        boot_rom: None,
+        save_dir: None,
+        capture_dir: None,
+        record: false,
+        cheats: None,
+        record_movie: None,
+        play_movie: None,
+        verify_movie: false,
+        log_level: LevelFilter::Warn,
+        paused: false,
+        fast_forward: 4.0,
+        slow_motion: 0.5,
+        show_fps: false,
+        strict: false,
+        trace: None,
+        trace_ly_stub: false,
+        trace_symbols: false,
+        debug: false,
+        gdb: None,
+        profile: None,
+        cdl: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("{} needs a value", arg));
+        match arg.as_str() {
+            "--scale" => {
+                let scale = value()?;
+                options.scale = match scale.parse() {
+                    Ok(scale) if scale > 0 => scale,
+                    _ => return Err(anyhow!("Invalid scale {}", scale)),
+                };
+            }
+            "--scaling" => options.scaling = value()?.parse()?,
+            "--fullscreen" => options.fullscreen = true,
+            "--filter" => {
+                let name = value()?;
+                options.filter = Filter::by_name(&name).ok_or_else(|| {
+                    anyhow!(
+                        "Unknown filter {}, expected one of {}",
+                        name,
+                        FILTERS.iter().map(|(name, _)| name).join(", ")
+                    )
+                })?;
+            }
+            "--scanlines" => options.scanlines = true,
+            "--blend" => options.blend = Some(parse_weights(&value()?)?),
+            "--palette" => palette = Some(value()?),
+            "--palette-file" => options
+                .palettes
+                .extend(Palette::load(Path::new(&value()?))?),
+            "--keys" => options.bindings = Bindings::load(Path::new(&value()?))?,
+            "--boot-rom" => options.boot_rom = Some(value()?.into()),
+            "--save-dir" => options.save_dir = Some(value()?.into()),
+            "--capture-dir" | "--screenshot-dir" => options.capture_dir = Some(value()?.into()),
+            "--record" => options.record = true,
+            "--cheats" => options.cheats = Some(value()?.into()),
+            "--record-movie" => options.record_movie = Some(value()?.into()),
+            "--play-movie" => options.play_movie = Some(value()?.into()),
+            "--verify-movie" => options.verify_movie = true,
+            "--log-level" => {
+                let level = value()?;
+                options.log_level = level
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid log level {}", level))?;
+            }
+            "--paused" => options.paused = true,
+            "--fast-forward" => options.fast_forward = parse_speed(&value()?, true)?,
+            "--slow-motion" => options.slow_motion = parse_speed(&value()?, false)?,
+            "--show-fps" => options.show_fps = true,
+            "--strict" => options.strict = true,
+            "--trace" => options.trace = Some(value()?.into()),
+            "--trace-ly-stub" => options.trace_ly_stub = true,
+            "--trace-symbols" => options.trace_symbols = true,
+            "--debug" => options.debug = true,
+            "--gdb" => {
+                let port = value()?;
+                options.gdb = Some(port.parse().map_err(|_| anyhow!("Invalid port {}", port))?);
+            }
+            "--profile" => options.profile = Some(value()?),
+            "--cdl" => options.cdl = Some(value()?.into()),
+            "--help" | "-h" => return Ok(None),
+            _ if arg.starts_with('-') => return Err(anyhow!("Unknown option {}", arg)),
+            _ if rom.is_none() => rom = Some(PathBuf::from(arg)),
+            _ => return Err(anyhow!("Unexpected argument {}", arg)),
+        }
+    }
+    options.rom = rom.ok_or_else(|| anyhow!("Missing ROM path"))?;
+    if options.verify_movie && options.play_movie.is_none() {
+        return Err(anyhow!("--verify-movie needs --play-movie"));
+    }
+    if options.play_movie.is_some() && options.record_movie.is_some() {
+        return Err(anyhow!("--play-movie and --record-movie can't be combined"));
+    }
+    if let Some(name) = palette {
+        // Custom palettes come last, so they win over presets with the same name.
+        options.palette = options
+            .palettes
+            .iter()
+            .rposition(|(palette, _)| *palette == name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown palette {}, expected one of {}",
+                    name,
+                    options.palettes.iter().map(|(name, _)| name).join(", ")
+                )
+            })?;
+    }
+    Ok(Some(options))
+}
+
+/// A multiple of real time, where 0 means unlimited if `allow_unlimited`.
+fn parse_speed(text: &str, allow_unlimited: bool) -> Result<f64> {
+    match text.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        Ok(speed) if speed == 0.0 && allow_unlimited => Ok(speed),
+        _ => Err(anyhow!("Invalid speed {}", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_args;
+    use gb::Palette;
+    use log::LevelFilter;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempdir::TempDir;
+
+    fn parse(args: &[&str]) -> anyhow::Result<Option<super::Options>> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() -> anyhow::Result<()> {
+        let options = parse(&[
+            "tetris.gb",
+            "--scale",
+            "4",
+            "--palette",
+            "dmg",
+            "--log-level",
+            "debug",
+            "--paused",
+        ])?
+        .unwrap();
+        assert_eq!(options.rom, PathBuf::from("tetris.gb"));
+        assert_eq!(options.scale, 4);
+        assert_eq!(
+            options.palettes[options.palette].1,
+            Palette::preset("dmg").unwrap()
+        );
+        assert_eq!(options.log_level, LevelFilter::Debug);
+        assert!(options.paused);
+
+        let options = parse(&["tetris.gb", "--screenshot-dir", "shots"])?.unwrap();
+        assert_eq!(options.capture_dir, Some(PathBuf::from("shots")));
+
+        assert!(parse(&["--help"])?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_arguments() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["a.gb", "b.gb"]).is_err());
+        assert!(parse(&["a.gb", "--scale", "0"]).is_err());
+        assert!(parse(&["a.gb", "--palette", "sepia"]).is_err());
+        assert!(parse(&["a.gb", "--gdb"]).is_err());
+        assert!(parse(&["a.gb", "--scaling", "bilinear"]).is_err());
+        assert!(parse(&["a.gb", "--filter", "hq4x"]).is_err());
+        assert!(parse(&["a.gb", "--slow-motion", "0"]).is_err());
+        assert!(parse(&["a.gb", "--fast-forward", "0"]).is_ok());
+        assert!(parse(&["a.gb", "--palette-file", "missing.pal"]).is_err());
+        assert!(parse(&["a.gb", "--keys", "missing.keys"]).is_err());
+        assert!(parse(&["a.gb", "--verify-movie"]).is_err());
+        assert!(parse(&[
+            "a.gb",
+            "--play-movie",
+            "a.movie",
+            "--record-movie",
+            "b.movie"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_custom_palette_overrides_preset() -> anyhow::Result<()> {
+        let dir = TempDir::new("boyohboy")?;
+        let path = dir.path().join("custom.pal");
+        fs::write(&path, "[dmg]\nbg = FFFFFF AAAAAA 555555 000000\n")?;
+        let options = parse(&[
+            "a.gb",
+            "--palette",
+            "dmg",
+            "--palette-file",
+            path.to_str().unwrap(),
+        ])?
+        .unwrap();
+        assert_eq!(options.palette, options.palettes.len() - 1);
+        assert_eq!(options.palettes[options.palette].1, Palette::default());
+        Ok(())
+    }
+}
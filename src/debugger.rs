@@ -0,0 +1,622 @@
+use anyhow::{anyhow, Result};
+use gb::{Cheat, FrameKind, GameBoy, WatchAction, WatchHit, WatchKind, Watchpoint};
+use itertools::Itertools;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+/// Instructions `disas` shows before PC when not given an address.
+const DISAS_CONTEXT: usize = 3;
+
+const HELP: &str = "\
+break <addr>         set a breakpoint (b); addresses can also be .sym labels, and
+                     bank:addr or a banked label only breaks in that ROM bank
+delete <addr>        remove a breakpoint
+breakpoints          list breakpoints
+watch <addr>[-<end>] [r|w|rw] [=<byte>] [log]
+                     break (or log) on CPU accesses, writes by default (w)
+unwatch <n>          remove watchpoint n
+watchpoints          list watchpoints
+step [n]             execute n instructions (s)
+next                 step over CALL/RST (n)
+finish               run until the current function returns
+continue             run until a breakpoint (c)
+regs                 dump registers (r)
+x <addr> [len]       hexdump memory
+set <addr> <byte>..  write memory
+disas [addr] [n]     disassemble n instructions, by default from a few before PC (d)
+bt                   backtrace
+cheats               list cheats
+cheat add <codes> [description]
+                     add Game Genie or GameShark codes, joined by +
+cheat on|off|del <n> enable, disable or remove cheat n
+quit                 exit (q)";
+
+/// A breakpoint, and for the switchable ROM region the bank it is in when known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Breakpoint {
+    address: u16,
+    bank: Option<u16>,
+}
+
+impl Breakpoint {
+    fn matches(&self, pc: u16, rom_bank: u16) -> bool {
+        pc == self.address && self.bank.is_none_or(|bank| bank == rom_bank)
+    }
+}
+
+enum Mode {
+    Paused,
+    Continue,
+    Step(usize),
+    Next { depth: usize },
+    Finish { depth: usize },
+}
+
+enum Prompt {
+    Again,
+    Resume(Mode),
+    Quit,
+}
+
+pub struct Debugger {
+    mode: Mode,
+    breakpoints: BTreeSet<Breakpoint>,
+    /// Set when resuming so the instruction we stopped on runs instead of immediately re-breaking.
+    resumed: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            mode: Mode::Paused,
+            breakpoints: BTreeSet::new(),
+            resumed: false,
+        }
+    }
+
+    /// Called before every `GameBoy::step`. Blocks on the REPL while paused and returns `false`
+    /// once the user quits.
+    pub fn before_step(&mut self, gb: &mut GameBoy) -> Result<bool> {
+        if self.should_pause(gb) {
+            self.mode = Mode::Paused;
+            return self.repl(gb);
+        }
+        Ok(true)
+    }
+
+    fn should_pause(&mut self, gb: &mut GameBoy) -> bool {
+        if std::mem::take(&mut self.resumed) {
+            return false;
+        }
+
+        let hits = gb.take_watch_hits();
+        for hit in &hits {
+            println!("{}", describe_watch_hit(hit));
+        }
+        if !hits.is_empty() {
+            return true;
+        }
+
+        let pc = gb.registers().pc;
+        let rom_bank = gb.rom_bank();
+        if self
+            .breakpoints
+            .iter()
+            .any(|breakpoint| breakpoint.matches(pc, rom_bank))
+            && !matches!(self.mode, Mode::Paused)
+        {
+            println!("Breakpoint at {}", location(gb, pc));
+            return true;
+        }
+
+        let depth = gb.call_stack().len();
+        match &mut self.mode {
+            Mode::Paused => true,
+            Mode::Continue => false,
+            Mode::Step(remaining) => {
+                *remaining -= 1;
+                *remaining == 0
+            }
+            Mode::Next { depth: start } => depth <= *start,
+            Mode::Finish { depth: start } => depth < *start,
+        }
+    }
+
+    fn repl(&mut self, gb: &mut GameBoy) -> Result<bool> {
+        self.print_location(gb)?;
+        let stdin = io::stdin();
+        loop {
+            print!("(gb) ");
+            io::stdout().flush()?;
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line)? == 0 {
+                return Ok(false);
+            }
+            match self.execute(gb, line.trim()) {
+                Ok(Prompt::Again) => {}
+                Ok(Prompt::Resume(mode)) => {
+                    self.mode = mode;
+                    self.resumed = true;
+                    return Ok(true);
+                }
+                Ok(Prompt::Quit) => return Ok(false),
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+
+    fn execute(&mut self, gb: &mut GameBoy, line: &str) -> Result<Prompt> {
+        let mut args = line.split_whitespace();
+        let command = match args.next() {
+            Some(command) => command,
+            None => return Ok(Prompt::Again),
+        };
+        let args: Vec<&str> = args.collect();
+
+        Ok(match command {
+            "break" | "b" => {
+                let breakpoint = parse_breakpoint(gb, arg(&args, 0)?)?;
+                self.breakpoints.insert(breakpoint);
+                println!("Breakpoint at {}", breakpoint_location(gb, &breakpoint));
+                Prompt::Again
+            }
+            "delete" => {
+                let breakpoint = parse_breakpoint(gb, arg(&args, 0)?)?;
+                if !self.breakpoints.remove(&breakpoint) {
+                    println!("No breakpoint at {}", breakpoint_location(gb, &breakpoint));
+                }
+                Prompt::Again
+            }
+            "breakpoints" => {
+                for breakpoint in &self.breakpoints {
+                    println!("{}", breakpoint_location(gb, breakpoint));
+                }
+                Prompt::Again
+            }
+            "watch" | "w" => {
+                let watchpoint = parse_watchpoint(gb, &args)?;
+                gb.add_watchpoint(watchpoint);
+                println!("Watchpoint {}", gb.watchpoints().len() - 1);
+                Prompt::Again
+            }
+            "unwatch" => {
+                let n: usize = arg(&args, 0)?.parse()?;
+                let watchpoint = *gb
+                    .watchpoints()
+                    .get(n)
+                    .ok_or_else(|| anyhow!("No watchpoint {}", n))?;
+                gb.remove_watchpoint(&watchpoint);
+                Prompt::Again
+            }
+            "watchpoints" => {
+                for (i, watchpoint) in gb.watchpoints().iter().enumerate() {
+                    println!("{}: {}", i, describe_watchpoint(watchpoint));
+                }
+                Prompt::Again
+            }
+            "step" | "s" => {
+                let count = args.first().map(|n| n.parse()).transpose()?.unwrap_or(1);
+                if count == 0 {
+                    return Err(anyhow!("Step count must be positive"));
+                }
+                Prompt::Resume(Mode::Step(count))
+            }
+            "next" | "n" => Prompt::Resume(Mode::Next {
+                depth: gb.call_stack().len(),
+            }),
+            "finish" => {
+                let depth = gb.call_stack().len();
+                if depth == 0 {
+                    return Err(anyhow!("Not inside a function"));
+                }
+                Prompt::Resume(Mode::Finish { depth })
+            }
+            "continue" | "c" => Prompt::Resume(Mode::Continue),
+            "regs" | "r" => {
+                println!("{}", gb.registers());
+                Prompt::Again
+            }
+            "x" => {
+                let addr = parse_address(gb, arg(&args, 0)?)?;
+                let len = args.get(1).map(|n| parse_u16(n)).transpose()?.unwrap_or(64);
+                self.hexdump(gb, addr, len)?;
+                Prompt::Again
+            }
+            "set" => {
+                let addr = parse_address(gb, arg(&args, 0)?)?;
+                if args.len() < 2 {
+                    return Err(anyhow!("Missing value"));
+                }
+                for (offset, val) in args[1..].iter().enumerate() {
+                    let val = u8::from_str_radix(val.trim_start_matches(['$', '#']), 16)?;
+                    gb.write_memory(addr.wrapping_add(offset as u16), val)?;
+                }
+                Prompt::Again
+            }
+            "disas" | "d" => {
+                let count = args.get(1).map(|n| n.parse()).transpose()?.unwrap_or(10);
+                match args.first() {
+                    Some(addr) => {
+                        let addr = parse_address(gb, addr)?;
+                        self.disassemble(gb, addr, count)?;
+                    }
+                    None => {
+                        let (start, before) = context_start(gb, gb.registers().pc)?;
+                        self.disassemble(gb, start, before + count)?;
+                    }
+                }
+                Prompt::Again
+            }
+            "bt" => {
+                self.backtrace(gb);
+                Prompt::Again
+            }
+            "cheats" => {
+                for (i, cheat) in gb.cheats().iter().enumerate() {
+                    let state = if cheat.enabled { "on" } else { "off" };
+                    println!("{}: {} {} {}", i, state, cheat.code, cheat.description);
+                }
+                Prompt::Again
+            }
+            "cheat" => {
+                match arg(&args, 0)? {
+                    "add" => {
+                        let cheat = Cheat::new(arg(&args, 1)?, &args[2..].join(" "))?;
+                        gb.add_cheat(cheat)?;
+                        println!("Cheat {}", gb.cheats().len() - 1);
+                    }
+                    "on" => gb.set_cheat_enabled(arg(&args, 1)?.parse()?, true)?,
+                    "off" => gb.set_cheat_enabled(arg(&args, 1)?.parse()?, false)?,
+                    "del" => {
+                        gb.remove_cheat(arg(&args, 1)?.parse()?)?;
+                    }
+                    action => return Err(anyhow!("Unknown cheat action {}", action)),
+                }
+                Prompt::Again
+            }
+            "help" | "h" => {
+                println!("{}", HELP);
+                Prompt::Again
+            }
+            "quit" | "q" => Prompt::Quit,
+            _ => return Err(anyhow!("Unknown command {}, try help", command)),
+        })
+    }
+
+    fn print_location(&self, gb: &mut GameBoy) -> Result<()> {
+        println!("{}", gb.registers());
+        if let Some(label) = gb.symbolize(gb.registers().pc) {
+            println!("in {}", label);
+        }
+        self.disassemble(gb, gb.registers().pc, 1)
+    }
+
+    fn disassemble(&self, gb: &mut GameBoy, addr: u16, count: usize) -> Result<()> {
+        let pc = gb.registers().pc;
+        let mut addr = addr;
+        for _ in 0..count {
+            if let Some(label) = gb
+                .symbols()
+                .label(addr, gb.rom_bank())
+                .filter(|_| count > 1)
+            {
+                println!("{}:", label);
+            }
+            let disassembly = gb.disassemble(addr)?;
+            println!(
+                "{} {:04X}: {:<9} {}",
+                if addr == pc { "=>" } else { "  " },
+                addr,
+                disassembly
+                    .bytes
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .join(" "),
+                disassembly.text
+            );
+            addr = addr.wrapping_add(disassembly.bytes.len() as u16);
+        }
+        Ok(())
+    }
+
+    fn hexdump(&self, gb: &mut GameBoy, addr: u16, len: u16) -> Result<()> {
+        let bytes = (0..len)
+            .map(|offset| gb.read_memory(addr.wrapping_add(offset)))
+            .collect::<Result<Vec<u8>>>()?;
+        for (line, chunk) in bytes.chunks(16).enumerate() {
+            println!(
+                "{:04X}: {:<47}  {}",
+                addr.wrapping_add(line as u16 * 16),
+                chunk.iter().map(|byte| format!("{:02X}", byte)).join(" "),
+                chunk
+                    .iter()
+                    .map(|byte| if byte.is_ascii_graphic() {
+                        *byte as char
+                    } else {
+                        '.'
+                    })
+                    .collect::<String>()
+            );
+        }
+        Ok(())
+    }
+
+    fn backtrace(&self, gb: &GameBoy) {
+        println!("#0  {}", location(gb, gb.registers().pc));
+        for (i, frame) in gb.call_stack().iter().rev().enumerate() {
+            let kind = match frame.kind {
+                FrameKind::Call => "call",
+                FrameKind::Rst => "rst",
+                FrameKind::Interrupt => "interrupt",
+            };
+            println!(
+                "#{:<2} {}  {} from {}, returns to {}",
+                i + 1,
+                location_in_bank(gb, frame.target, frame.bank),
+                kind,
+                location_in_bank(gb, frame.caller, frame.bank),
+                location(gb, frame.return_address)
+            );
+        }
+    }
+}
+
+fn arg<'a>(args: &[&'a str], i: usize) -> Result<&'a str> {
+    args.get(i)
+        .copied()
+        .ok_or_else(|| anyhow!("Missing argument {}", i + 1))
+}
+
+/// Where to start disassembling to show up to `DISAS_CONTEXT` instructions before `pc`, and how
+/// many there are. Instructions have no markers, so this backs up as far as those could reach
+/// and takes the first start whose instructions line up with `pc`.
+fn context_start(gb: &mut GameBoy, pc: u16) -> Result<(u16, usize)> {
+    for start in pc.saturating_sub(3 * DISAS_CONTEXT as u16)..pc {
+        let mut starts = vec![];
+        let mut addr = start;
+        while addr < pc {
+            starts.push(addr);
+            addr = addr.saturating_add(gb.disassemble(addr)?.bytes.len() as u16);
+        }
+        if addr == pc {
+            let skip = starts.len().saturating_sub(DISAS_CONTEXT);
+            return Ok((starts[skip], starts.len() - skip));
+        }
+    }
+    Ok((pc, 0))
+}
+
+/// Parses `<addr>[-<end>] [r|w|rw] [=<byte>] [log]`.
+fn parse_watchpoint(gb: &GameBoy, args: &[&str]) -> Result<Watchpoint> {
+    let range = arg(args, 0)?;
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (parse_address(gb, start)?, parse_address(gb, end)?),
+        None => (parse_address(gb, range)?, parse_address(gb, range)?),
+    };
+    if end < start {
+        return Err(anyhow!("Empty range {}", range));
+    }
+    let mut watchpoint = Watchpoint {
+        start,
+        end,
+        kind: WatchKind::Write,
+        value: None,
+        action: WatchAction::Break,
+    };
+    for option in &args[1..] {
+        match *option {
+            "r" => watchpoint.kind = WatchKind::Read,
+            "w" => watchpoint.kind = WatchKind::Write,
+            "rw" => watchpoint.kind = WatchKind::Access,
+            "log" => watchpoint.action = WatchAction::Log,
+            _ => match option.strip_prefix('=') {
+                Some(value) => {
+                    watchpoint.value = Some(u8::from_str_radix(
+                        value.trim_start_matches(['$', '#']),
+                        16,
+                    )?)
+                }
+                None => return Err(anyhow!("Unknown watch option {}", option)),
+            },
+        }
+    }
+    Ok(watchpoint)
+}
+
+fn describe_watchpoint(watchpoint: &Watchpoint) -> String {
+    let kind = match watchpoint.kind {
+        WatchKind::Read => "r",
+        WatchKind::Write => "w",
+        WatchKind::Access => "rw",
+    };
+    let mut description = format!("{:04X}-{:04X} {}", watchpoint.start, watchpoint.end, kind);
+    if let Some(value) = watchpoint.value {
+        description.push_str(&format!(" ={:02X}", value));
+    }
+    if watchpoint.action == WatchAction::Log {
+        description.push_str(" log");
+    }
+    description
+}
+
+pub fn describe_watch_hit(hit: &WatchHit) -> String {
+    match hit.access {
+        WatchKind::Write => format!(
+            "Write {:04X}: {:02X} -> {:02X} at PC {:04X}, cycle {}",
+            hit.address, hit.old, hit.new, hit.pc, hit.cycles
+        ),
+        _ => format!(
+            "Read {:04X}: {:02X} at PC {:04X}, cycle {}",
+            hit.address, hit.new, hit.pc, hit.cycles
+        ),
+    }
+}
+
+/// `0150 <Main+2>`, or just the address without a matching symbol.
+fn location(gb: &GameBoy, addr: u16) -> String {
+    location_in_bank(gb, addr, gb.rom_bank())
+}
+
+/// Like `location`, for an address in `bank` rather than the one currently mapped.
+fn location_in_bank(gb: &GameBoy, addr: u16, bank: u16) -> String {
+    match gb.symbols().symbolize(addr, bank) {
+        Some(label) => format!("{:04X} <{}>", addr, label),
+        None => format!("{:04X}", addr),
+    }
+}
+
+/// `02:4000 <Banked>` for a breakpoint in a given bank, else like `location`.
+fn breakpoint_location(gb: &GameBoy, breakpoint: &Breakpoint) -> String {
+    match breakpoint.bank {
+        Some(bank) => format!(
+            "{:02X}:{}",
+            bank,
+            location_in_bank(gb, breakpoint.address, bank)
+        ),
+        None => location(gb, breakpoint.address),
+    }
+}
+
+/// Parses a label from the `.sym` file, or else a hex address. Labels win so that e.g. `Add`
+/// isn't read as 0x0ADD.
+fn parse_address(gb: &GameBoy, s: &str) -> Result<u16> {
+    match gb.resolve_symbol(s) {
+        Some((_, addr)) => Ok(addr),
+        None => parse_u16(s),
+    }
+}
+
+/// Like `parse_address`, but also accepts `bank:addr`. The bank is kept for addresses in the
+/// switchable ROM region, where the same address is different code in every bank.
+fn parse_breakpoint(gb: &GameBoy, s: &str) -> Result<Breakpoint> {
+    let (bank, address) = match (gb.resolve_symbol(s), s.split_once(':')) {
+        (Some((bank, address)), _) => (Some(bank), address),
+        (None, Some((bank, address))) => (
+            Some(u16::from_str_radix(bank, 16).map_err(|_| anyhow!("Invalid bank {}", bank))?),
+            parse_u16(address)?,
+        ),
+        (None, None) => (None, parse_u16(s)?),
+    };
+    Ok(Breakpoint {
+        address,
+        bank: bank.filter(|_| (0x4000..=0x7FFF).contains(&address)),
+    })
+}
+
+/// Parses a hex address, with an optional `$` or `0x` prefix.
+fn parse_u16(s: &str) -> Result<u16> {
+    let digits = s
+        .strip_prefix('$')
+        .or_else(|| s.strip_prefix("0x"))
+        .unwrap_or(s);
+    u16::from_str_radix(digits, 16).map_err(|_| anyhow!("Invalid address {}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{context_start, Debugger, Mode, Prompt};
+    use gb::GameBoy;
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn run_command(debugger: &mut Debugger, gb: &mut GameBoy, line: &str) -> anyhow::Result<()> {
+        if let Prompt::Resume(mode) = debugger.execute(gb, line)? {
+            debugger.mode = mode;
+            debugger.resumed = true;
+        }
+        while !debugger.should_pause(gb) {
+            gb.step()?;
+        }
+        debugger.mode = Mode::Paused;
+        Ok(())
+    }
+
+    #[test]
+    fn test_disassembly_context() -> anyhow::Result<()> {
+        let dir = TempDir::new("boyohboy")?;
+        let path = dir.path().join("context.gb");
+        let mut rom = vec![0u8; 0x8000];
+        // ld a, $3E; ld hl, $1234; inc a; inc a; ld b, a, after the zeros (NOPs) of the header.
+        rom[0x0100..0x0108].copy_from_slice(&[0x3E, 0x3E, 0x21, 0x34, 0x12, 0x3C, 0x3C, 0x47]);
+        fs::write(&path, rom)?;
+        let mut gb = GameBoy::new(&path)?;
+
+        assert_eq!(context_start(&mut gb, 0x0100)?, (0x00FD, 3));
+        assert_eq!(context_start(&mut gb, 0x0105)?, (0x00FF, 3));
+        assert_eq!(context_start(&mut gb, 0x0107)?, (0x0102, 3));
+        // Only a start inside ld a, $3E lines up with an address inside ld hl.
+        assert_eq!(context_start(&mut gb, 0x0103)?, (0x0101, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_and_finish() -> anyhow::Result<()> {
+        let dir = TempDir::new("boyohboy")?;
+        let path = dir.path().join("calls.gb");
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x0100..0x0104].copy_from_slice(&[0xCD, 0x00, 0x02, 0x00]);
+        rom[0x0200..0x0203].copy_from_slice(&[0x00, 0x00, 0xC9]);
+        fs::write(&path, rom)?;
+        let mut gb = GameBoy::new(&path)?;
+        let mut debugger = Debugger::new();
+
+        run_command(&mut debugger, &mut gb, "next")?;
+        assert_eq!(gb.registers().pc, 0x0103);
+
+        let mut gb = GameBoy::new(&path)?;
+        run_command(&mut debugger, &mut gb, "step")?;
+        assert_eq!(gb.registers().pc, 0x0200);
+        assert_eq!(gb.call_stack().len(), 1);
+
+        run_command(&mut debugger, &mut gb, "finish")?;
+        assert_eq!(gb.registers().pc, 0x0103);
+        assert!(gb.call_stack().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_banked_breakpoints() -> anyhow::Result<()> {
+        let dir = TempDir::new("boyohboy")?;
+        let path = dir.path().join("banked.gb");
+        let mut rom = vec![0u8; 0x8000];
+        // call $4000; jr @
+        rom[0x0100..0x0105].copy_from_slice(&[0xCD, 0x00, 0x40, 0x18, 0xFE]);
+        rom[0x4000] = 0xC9;
+        fs::write(&path, rom)?;
+        fs::write(
+            path.with_extension("sym"),
+            "01:4000 InBank1\n02:4000 InBank2\n",
+        )?;
+
+        let mut gb = GameBoy::new(&path)?;
+        let mut debugger = Debugger::new();
+        run_command(&mut debugger, &mut gb, "break InBank2")?;
+        run_command(&mut debugger, &mut gb, "break 0103")?;
+        run_command(&mut debugger, &mut gb, "continue")?;
+        assert_eq!(gb.registers().pc, 0x0103);
+
+        let mut gb = GameBoy::new(&path)?;
+        let mut debugger = Debugger::new();
+        run_command(&mut debugger, &mut gb, "break 01:4000")?;
+        run_command(&mut debugger, &mut gb, "continue")?;
+        assert_eq!(gb.registers().pc, 0x4000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_pauses_after_matching_write() -> anyhow::Result<()> {
+        let dir = TempDir::new("boyohboy")?;
+        let path = dir.path().join("watch.gb");
+        let mut rom = vec![0u8; 0x8000];
+        // ld hl, $C000; ld [hl], $01; ld [hl], $02; nop
+        rom[0x0100..0x0108].copy_from_slice(&[0x21, 0x00, 0xC0, 0x36, 0x01, 0x36, 0x02, 0x00]);
+        fs::write(&path, rom)?;
+        let mut gb = GameBoy::new(&path)?;
+        let mut debugger = Debugger::new();
+
+        run_command(&mut debugger, &mut gb, "watch C000-C0FF w =02")?;
+        run_command(&mut debugger, &mut gb, "continue")?;
+        assert_eq!(gb.registers().pc, 0x0107);
+        Ok(())
+    }
+}
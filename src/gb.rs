@@ -5,23 +5,53 @@ use crate::gb::gpu::Gpu;
 
 use crate::gb::memory::Memory;
 
+use crate::gb::call_stack::CallStack;
 use crate::gb::clock::Clock;
-use crate::gb::cpu::{Cpu, InstructionResult};
+use crate::gb::cpu::disassembler::disassemble_with_labels;
 use crate::gb::memory::map::{SB, SC};
+use crate::gb::movie::{crc32, describe_buttons, MovieMode, MovieSession};
 use crate::gb::Halt::Running;
+use anyhow::anyhow;
+use log::warn;
+use std::io::Write;
 use std::ops;
 use std::path::Path;
-use Halt::{Bug, Halted};
+use Halt::{Bug, Halted, Locked};
+
+pub use crate::gb::bus::{Bus, Fetch, FlatRam};
+pub use crate::gb::call_stack::{Frame, FrameKind};
+pub use crate::gb::cpu::disassembler::Disassembly;
+pub use crate::gb::cpu::{Branch, Cpu, InstructionResult, InterruptResult, Registers};
+pub use crate::gb::filter::{Filter, Image, FILTERS};
+pub use crate::gb::memory::cheats::{Cheat, CheatCode};
+pub use crate::gb::memory::code_data_log::{CodeDataLog, CDL_DATA, CDL_OPCODE, CDL_OPERAND};
+pub use crate::gb::memory::joypad::{Button, BUTTONS};
+pub use crate::gb::memory::watchpoint::{WatchAction, WatchHit, WatchKind, Watchpoint};
+pub use crate::gb::movie::{Movie, MovieFrame};
+pub use crate::gb::palette::{Palette, Shades, PRESETS as PALETTE_PRESETS};
+pub use crate::gb::profiler::Profiler;
+pub use crate::gb::recorder::Recorder;
+pub use crate::gb::screen::{FrameBuffer, CYCLES_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH};
+pub use crate::gb::symbols::Symbols;
 
 mod bits;
+mod bus;
+mod call_stack;
 mod clock;
 mod cpu;
+mod filter;
 mod gpu;
 mod memory;
+mod movie;
+mod palette;
+mod profiler;
+mod recorder;
+mod screen;
+mod symbols;
 
 const R16_HL: u8 = 2;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
     White,
     LightGray,
@@ -29,11 +59,26 @@ pub enum Color {
     Black,
 }
 
+#[derive(Debug)]
+pub enum DebugEvent {
+    Lockup { pc: u16, opcode: u8 },
+    Watchpoint(WatchHit),
+}
+
+/// The palette register a pixel's shade was mapped through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Background,
+    Object0,
+    Object1,
+}
+
 #[derive(Debug)]
 pub struct Pixel {
     pub x: u8,
     pub y: u8,
     pub color: Color,
+    pub layer: Layer,
 }
 
 enum AccessType {
@@ -54,22 +99,532 @@ impl ops::Add<AccessType> for AccessType {
 
 pub struct GameBoy {
     gb: GameBoyImpl,
+    frame_buffer: FrameBuffer,
+    frames: u64,
+    recorder: Option<Recorder>,
+    boot_rom_crc32: Option<u32>,
+    movie: Option<MovieSession>,
 }
 
 impl GameBoy {
     pub fn new(cartridge: &Path) -> Result<GameBoy> {
         Ok(GameBoy {
             gb: GameBoyImpl::new(cartridge)?,
+            frame_buffer: FrameBuffer::new(),
+            frames: 0,
+            recorder: None,
+            boot_rom_crc32: None,
+            movie: None,
         })
     }
 
+    /// Maps a 256 byte DMG boot ROM over the cartridge and resets the CPU to run it. Call before
+    /// the first step.
+    pub fn load_boot_rom(&mut self, path: &Path) -> Result<()> {
+        let boot_rom = std::fs::read(path)
+            .map_err(|e| anyhow!("Cannot read boot ROM {}: {}", path.display(), e))?;
+        if boot_rom.len() != 0x100 {
+            return Err(anyhow!(
+                "Boot ROM {} is {} bytes, expected 256",
+                path.display(),
+                boot_rom.len()
+            ));
+        }
+        self.boot_rom_crc32 = Some(crc32(&boot_rom));
+        self.gb.memory.boot_rom = Some(boot_rom);
+        self.gb.cpu.set_registers(Registers {
+            a: 0,
+            f: 0,
+            b: 0,
+            c: 0,
+            d: 0,
+            e: 0,
+            h: 0,
+            l: 0,
+            sp: 0,
+            pc: 0,
+            ime: false,
+        });
+        Ok(())
+    }
+
+    /// Whether the cartridge RAM is battery backed and worth saving.
+    pub fn has_battery(&self) -> bool {
+        self.gb.memory.has_battery()
+    }
+
+    pub fn external_ram(&self) -> &[u8] {
+        self.gb.memory.external_ram()
+    }
+
+    pub fn load_external_ram(&mut self, data: &[u8]) -> Result<()> {
+        self.gb.memory.load_external_ram(data)
+    }
+
     pub fn step(&mut self) -> Result<(Option<String>, Vec<Pixel>)> {
-        self.gb.step()
+        let frame = self.gb.clock.cycles() / CYCLES_PER_FRAME;
+        let result = self.gb.step()?;
+        self.gb.report_watch_hits();
+        // Once per frame's worth of cycles rather than per drawn frame, so that GameShark codes
+        // keep applying while the LCD is off.
+        if self.gb.clock.cycles() / CYCLES_PER_FRAME != frame {
+            self.gb.memory.apply_gameshark_codes()?;
+        }
+        for pixel in &result.1 {
+            if self.frame_buffer.draw(pixel) {
+                self.frames += 1;
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.write_frame(&self.frame_buffer)?;
+                }
+            }
+        }
+        self.advance_movie()?;
+        Ok(result)
+    }
+
+    /// Records or plays back the buttons of every movie frame that ended during the last step.
+    fn advance_movie(&mut self) -> Result<()> {
+        let Some(session) = self.movie.as_mut() else {
+            return Ok(());
+        };
+        while self.gb.clock.cycles() >= (session.frame + 1) * CYCLES_PER_FRAME {
+            let screen_hash = self.frame_buffer.hash();
+            match session.mode {
+                MovieMode::Recording { next_buttons } => {
+                    session.movie.frames.push(MovieFrame {
+                        buttons: self.gb.memory.buttons(),
+                        screen_hash,
+                    });
+                    self.gb.memory.set_buttons(next_buttons)?;
+                }
+                MovieMode::Playing { verify } => {
+                    let frame = session.movie.frames[session.frame];
+                    if verify && frame.screen_hash != screen_hash {
+                        return Err(anyhow!(
+                            "Movie desynced at frame {} (buttons {}): screen hash {:016X}, \
+                             recorded {:016X}",
+                            session.frame,
+                            describe_buttons(frame.buttons),
+                            screen_hash,
+                            frame.screen_hash
+                        ));
+                    }
+                    match session.movie.frames.get(session.frame + 1) {
+                        Some(next) => self.gb.memory.set_buttons(next.buttons)?,
+                        None => {
+                            self.movie = None;
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            session.frame += 1;
+        }
+        Ok(())
+    }
+
+    /// Runs until the last pixel of the frame is drawn, or for a frame's worth of cycles while the
+    /// LCD is off. Returns the serial output.
+    pub fn step_frame(&mut self) -> Result<String> {
+        let frames = self.frames;
+        let start = self.cycles();
+        let mut serial = String::new();
+        while self.frames == frames && self.cycles().wrapping_sub(start) <= CYCLES_PER_FRAME {
+            if let (Some(log), _) = self.step()? {
+                serial.push_str(&log);
+            }
+        }
+        Ok(serial)
+    }
+
+    pub fn frame_buffer(&self) -> &FrameBuffer {
+        &self.frame_buffer
+    }
+
+    /// Saves the screen as a PNG, `scale` times the native resolution.
+    pub fn save_screenshot(&self, path: &Path, palette: &Palette, scale: usize) -> Result<()> {
+        Image::new(&self.frame_buffer, palette)
+            .scale(scale)
+            .save_png(path)
+    }
+
+    /// Appends every completed frame to a `.y4m` video at `path` until `stop_recording`.
+    pub fn start_recording(&mut self, path: &Path, palette: &Palette) -> Result<()> {
+        self.stop_recording()?;
+        self.recorder = Some(Recorder::create(path, palette)?);
+        Ok(())
+    }
+
+    /// Finishes the recording, if any, returning how many frames it has.
+    pub fn stop_recording(&mut self) -> Result<Option<u64>> {
+        match self.recorder.take() {
+            Some(recorder) => {
+                let frames = recorder.frames();
+                recorder.finish()?;
+                Ok(Some(frames))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// The pressed buttons as a mask of `Button::mask` bits.
+    pub fn buttons(&self) -> u8 {
+        self.gb.memory.buttons()
+    }
+
+    /// Presses exactly the buttons in the mask `buttons`, releasing all others. While a movie is
+    /// recorded they are held from the next movie frame on, and while one plays they are ignored.
+    pub fn set_buttons(&mut self, buttons: u8) -> Result<()> {
+        match self.movie.as_mut().map(|session| &mut session.mode) {
+            Some(MovieMode::Recording { next_buttons }) => {
+                *next_buttons = buttons;
+                Ok(())
+            }
+            Some(MovieMode::Playing { .. }) => Ok(()),
+            None => self.gb.memory.set_buttons(buttons),
+        }
+    }
+
+    pub fn set_button(&mut self, button: Button, pressed: bool) -> Result<()> {
+        let buttons = match &self.movie {
+            Some(MovieSession {
+                mode: MovieMode::Recording { next_buttons },
+                ..
+            }) => *next_buttons,
+            _ => self.buttons(),
+        };
+        let buttons = match pressed {
+            true => buttons | button.mask(),
+            false => buttons & !button.mask(),
+        };
+        self.set_buttons(buttons)
+    }
+
+    /// CRC-32 of the ROM file, which movies are tied to.
+    pub fn rom_crc32(&self) -> u32 {
+        crc32(self.gb.memory.rom())
+    }
+
+    fn check_power_on(&self) -> Result<()> {
+        match self.cycles() {
+            0 => Ok(()),
+            _ => Err(anyhow!("Movies start at power on, before the first step")),
+        }
+    }
+
+    /// Starts recording the buttons held in every movie frame, a frame's worth of cycles. Call
+    /// before the first step, after any boot ROM and battery save are loaded.
+    pub fn start_movie_recording(&mut self) -> Result<()> {
+        self.check_power_on()?;
+        let mut movie = Movie::new(
+            self.rom_crc32(),
+            self.boot_rom_crc32,
+            crc32(self.external_ram()),
+        );
+        movie.cheats = self.enabled_cheat_codes();
+        self.movie = Some(MovieSession {
+            movie,
+            frame: 0,
+            mode: MovieMode::Recording {
+                next_buttons: self.buttons(),
+            },
+        });
+        Ok(())
+    }
+
+    /// Replays `movie` from power on, taking over the buttons until it ends. With `verify`, the
+    /// screen at the end of every frame must match the recorded hash, or stepping fails.
+    pub fn play_movie(&mut self, movie: Movie, verify: bool) -> Result<()> {
+        self.check_power_on()?;
+        if movie.rom_crc32 != self.rom_crc32() {
+            return Err(anyhow!(
+                "Movie is for ROM {:08X}, not {:08X}",
+                movie.rom_crc32,
+                self.rom_crc32()
+            ));
+        }
+        if movie.boot_rom_crc32 != self.boot_rom_crc32 {
+            return Err(match movie.boot_rom_crc32 {
+                Some(crc32) => anyhow!("Movie was recorded with boot ROM {:08X}", crc32),
+                None => anyhow!("Movie was recorded without a boot ROM"),
+            });
+        }
+        if movie.save_ram_crc32 != crc32(self.external_ram()) {
+            return Err(anyhow!(
+                "Movie was recorded with different cartridge RAM, check the battery save"
+            ));
+        }
+        if movie.cheats != self.enabled_cheat_codes() {
+            return Err(match movie.cheats.is_empty() {
+                true => anyhow!("Movie was recorded without cheats, disable them"),
+                false => anyhow!(
+                    "Movie was recorded with the cheats {}",
+                    movie.cheats.join(", ")
+                ),
+            });
+        }
+        if movie.version != env!("CARGO_PKG_VERSION") {
+            warn!(
+                "Movie was recorded with version {}, this is {}",
+                movie.version,
+                env!("CARGO_PKG_VERSION")
+            );
+        }
+        let Some(first) = movie.frames.first() else {
+            return Ok(());
+        };
+        self.gb.memory.set_buttons(first.buttons)?;
+        self.movie = Some(MovieSession {
+            movie,
+            frame: 0,
+            mode: MovieMode::Playing { verify },
+        });
+        Ok(())
+    }
+
+    /// Stops recording or playing back, returning the movie.
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        self.movie.take().map(|session| session.movie)
+    }
+
+    pub fn is_recording_movie(&self) -> bool {
+        matches!(
+            self.movie,
+            Some(MovieSession {
+                mode: MovieMode::Recording { .. },
+                ..
+            })
+        )
+    }
+
+    /// Whether a movie is still playing; playback stops by itself after the last frame.
+    pub fn is_playing_movie(&self) -> bool {
+        matches!(
+            self.movie,
+            Some(MovieSession {
+                mode: MovieMode::Playing { .. },
+                ..
+            })
+        )
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        self.gb.memory.cheats()
+    }
+
+    fn enabled_cheat_codes(&self) -> Vec<String> {
+        self.cheats()
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .map(|cheat| cheat.code.clone())
+            .collect()
+    }
+
+    /// Movies replay with the cheats they were recorded with, so those can't change meanwhile.
+    fn check_no_movie(&self) -> Result<()> {
+        match self.movie {
+            Some(_) => Err(anyhow!(
+                "Cheats can't change while a movie is recorded or played"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Game Genie codes take effect right away, GameShark codes after every frame's worth of
+    /// cycles.
+    pub fn add_cheat(&mut self, cheat: Cheat) -> Result<()> {
+        self.check_no_movie()?;
+        self.gb.memory.add_cheat(cheat);
+        Ok(())
+    }
+
+    pub fn remove_cheat(&mut self, index: usize) -> Result<Cheat> {
+        self.check_no_movie()?;
+        self.gb
+            .memory
+            .remove_cheat(index)
+            .ok_or_else(|| anyhow!("No cheat {}", index))
+    }
+
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) -> Result<()> {
+        self.check_no_movie()?;
+        match self.gb.memory.set_cheat_enabled(index, enabled) {
+            true => Ok(()),
+            false => Err(anyhow!("No cheat {}", index)),
+        }
+    }
+
+    /// Frames completed since power on.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// M-cycles since power on.
+    pub fn cycles(&self) -> usize {
+        self.gb.clock.cycles()
+    }
+
+    /// In strict mode illegal opcodes abort emulation instead of locking up the CPU.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.gb.strict = strict;
+    }
+
+    pub fn set_debug_hook(&mut self, hook: impl FnMut(&DebugEvent) + 'static) {
+        self.gb.debug_hook = Some(Box::new(hook));
+    }
+
+    /// Writes a gameboy-doctor line before every executed instruction, or stops tracing on `None`.
+    pub fn set_trace(&mut self, trace: Option<Box<dyn Write>>) {
+        self.gb.trace = trace;
+    }
+
+    /// Makes LY always read 0x90, as gameboy-doctor reference logs expect.
+    pub fn set_ly_stub(&mut self, ly_stub: bool) {
+        self.gb.memory.ly_stub = ly_stub;
+    }
+
+    /// Appends the symbolized PC to each trace line. gameboy-doctor won't accept these traces.
+    pub fn set_trace_symbols(&mut self, trace_symbols: bool) {
+        self.gb.trace_symbols = trace_symbols;
+    }
+
+    pub fn disassemble(&mut self, address: u16) -> Result<Disassembly> {
+        let symbols = &self.gb.symbols;
+        let rom_bank = self.gb.memory.rom_bank();
+        let memory = &mut self.gb.memory;
+        disassemble_with_labels(
+            |addr| memory.read(addr),
+            address,
+            |addr| symbols.label(addr, rom_bank).map(String::from),
+        )
+    }
+
+    /// Replaces the symbols loaded from the `.sym` file next to the ROM, if there was one.
+    pub fn load_symbols(&mut self, path: &Path) -> Result<()> {
+        self.gb.symbols = Symbols::load(path)?;
+        Ok(())
+    }
+
+    /// Starts accumulating cycles per address and call stack, discarding any previous profile, or
+    /// stops profiling on `false`.
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.gb.profiler = profiling.then(Profiler::new);
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.gb.profiler.as_ref()
+    }
+
+    /// Starts flagging every ROM byte the CPU executes or reads, or stops on `false`.
+    pub fn set_code_data_logging(&mut self, logging: bool) {
+        let rom_len = self.gb.memory.rom_len();
+        let cart_ram_len = self.external_ram().len();
+        self.gb.memory.code_data_log = logging.then(|| CodeDataLog::new(rom_len, cart_ram_len));
+    }
+
+    /// Starts logging from the flags saved by an earlier session.
+    pub fn load_code_data_log(&mut self, path: &Path) -> Result<()> {
+        let mut code_data_log =
+            CodeDataLog::new(self.gb.memory.rom_len(), self.external_ram().len());
+        code_data_log.merge(path)?;
+        self.gb.memory.code_data_log = Some(code_data_log);
+        Ok(())
+    }
+
+    pub fn code_data_log(&self) -> Option<&CodeDataLog> {
+        self.gb.memory.code_data_log.as_ref()
+    }
+
+    /// The ROM bank mapped at 0x4000.
+    pub fn rom_bank(&self) -> u16 {
+        self.gb.memory.rom_bank()
+    }
+
+    pub fn symbols(&self) -> &Symbols {
+        &self.gb.symbols
+    }
+
+    /// `address` as `label+offset` against the currently mapped bank.
+    pub fn symbolize(&self, address: u16) -> Option<String> {
+        self.gb
+            .symbols
+            .symbolize(address, self.gb.memory.rom_bank())
+    }
+
+    /// The `(bank, address)` of a label.
+    pub fn resolve_symbol(&self, label: &str) -> Option<(u16, u16)> {
+        self.gb.symbols.resolve(label)
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.gb.cpu.registers()
+    }
+
+    pub fn read_memory(&mut self, address: u16) -> Result<u8> {
+        self.gb.memory.read(address)
+    }
+
+    pub fn write_memory(&mut self, address: u16, val: u8) -> Result<()> {
+        self.gb.memory.write(address, val)
+    }
+
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.gb.cpu.set_registers(registers)
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.gb.memory.watchpoints.add(watchpoint)
+    }
+
+    /// Returns whether a matching watchpoint was removed.
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        self.gb.memory.watchpoints.remove(watchpoint)
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        self.gb.memory.watchpoints.watchpoints()
+    }
+
+    /// Hits on `WatchAction::Break` watchpoints since the last call, in the order the CPU made the
+    /// accesses. Every hit is also passed to the debug hook.
+    pub fn take_watch_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.gb.watch_breaks)
+    }
+
+    /// The CALL/RST/interrupt frames entered so far, innermost last.
+    pub fn call_stack(&self) -> &[Frame] {
+        self.gb.call_stack.frames()
     }
 }
 
+/// Loads `game.sym` next to `game.gb`. A broken symbol file shouldn't stop the game from running.
+fn load_symbols(cartridge: &Path) -> Symbols {
+    let path = cartridge.with_extension("sym");
+    if !path.is_file() {
+        return Symbols::new();
+    }
+    Symbols::load(&path).unwrap_or_else(|e| {
+        warn!("Ignoring symbols: {}", e);
+        Symbols::new()
+    })
+}
+
+type DebugHook = Box<dyn FnMut(&DebugEvent)>;
+
 struct GameBoyImpl {
     halt: Halt,
+    strict: bool,
+    debug_hook: Option<DebugHook>,
+    trace: Option<Box<dyn Write>>,
+    trace_symbols: bool,
+    symbols: Symbols,
+    watch_breaks: Vec<WatchHit>,
+    profiler: Option<Profiler>,
+    call_stack: CallStack,
     cpu: Cpu,
     clock: Clock,
     gpu: Gpu,
@@ -81,10 +636,35 @@ enum Halt {
     Running,
     Halted,
     Bug,
+    Locked,
 }
 
 impl GameBoyImpl {
     fn step(&mut self) -> Result<(Option<String>, Vec<Pixel>)> {
+        if self.halt == Locked {
+            let pixels = self.clock.tick(&mut self.gpu, &mut self.memory, 1)?;
+            return Ok((self.serial()?, pixels));
+        }
+
+        if self.halt != Halted {
+            if let Some(trace) = self.trace.as_mut() {
+                let mut line = self.cpu.trace_line(&mut self.memory)?;
+                if self.trace_symbols {
+                    if let Some(label) = self
+                        .symbols
+                        .symbolize(self.cpu.pc(), self.memory.rom_bank())
+                    {
+                        line.push_str(&format!(" ({})", label));
+                    }
+                }
+                writeln!(trace, "{}", line)?;
+            }
+        }
+
+        let pc = self.cpu.pc();
+        self.memory
+            .watchpoints
+            .set_instruction(pc, self.clock.cycles());
         let instruction_result = match self.halt {
             Running | Bug => {
                 if self.halt == Running {
@@ -96,9 +676,43 @@ impl GameBoyImpl {
             }
             Halted => InstructionResult {
                 is_halt: false,
+                is_lockup: false,
                 cycles: 1,
+                branch: None,
             },
+            Locked => unreachable!("a locked up CPU only ticks the clock, above"),
         };
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(
+                pc,
+                self.memory.rom_bank(),
+                self.call_stack.frames(),
+                instruction_result.cycles,
+            );
+        }
+        self.track_branch(pc, instruction_result.branch);
+
+        if instruction_result.is_lockup {
+            let opcode = self.memory.read(pc)?;
+            if self.strict {
+                return Err(anyhow!(
+                    "Illegal instruction {:#04X} at {:#06X}",
+                    opcode,
+                    pc
+                ));
+            }
+            if let Some(hook) = self.debug_hook.as_mut() {
+                hook(&DebugEvent::Lockup { pc, opcode });
+            }
+            self.halt = Locked;
+            let pixels = self.clock.tick(
+                &mut self.gpu,
+                &mut self.memory,
+                usize::from(instruction_result.cycles),
+            )?;
+            return Ok((self.serial()?, pixels));
+        }
+
         let mut pixels = Vec::with_capacity(usize::from(instruction_result.cycles) * 4 + 5 * 4);
         let mut instruction_pixels = self.clock.tick(
             &mut self.gpu,
@@ -106,7 +720,28 @@ impl GameBoyImpl {
             usize::from(instruction_result.cycles),
         )?;
 
+        let interrupted_pc = self.cpu.pc();
+        self.memory
+            .watchpoints
+            .set_instruction(interrupted_pc, self.clock.cycles());
         let interrupt_result = self.cpu.handle_interrupts(&mut self.memory)?;
+        if interrupt_result.cycles > 0 {
+            self.track_branch(
+                interrupted_pc,
+                Some(Branch::Call {
+                    kind: FrameKind::Interrupt,
+                    return_address: interrupted_pc,
+                }),
+            );
+            if let Some(profiler) = self.profiler.as_mut() {
+                profiler.record(
+                    self.cpu.pc(),
+                    self.memory.rom_bank(),
+                    self.call_stack.frames(),
+                    interrupt_result.cycles,
+                );
+            }
+        }
         let mut interrupt_pixels = self.clock.tick(
             &mut self.gpu,
             &mut self.memory,
@@ -128,6 +763,7 @@ impl GameBoyImpl {
                 Halted => Halted,
                 Bug => Running,
                 Running => Running,
+                Locked => Locked,
             },
         };
         Ok((self.serial()?, pixels))
@@ -136,6 +772,14 @@ impl GameBoyImpl {
     pub fn new(cartridge: &Path) -> Result<GameBoyImpl> {
         let gb = GameBoyImpl {
             halt: Running,
+            strict: false,
+            debug_hook: None,
+            trace: None,
+            trace_symbols: false,
+            symbols: load_symbols(cartridge),
+            watch_breaks: vec![],
+            profiler: None,
+            call_stack: CallStack::new(),
             clock: Clock::new(),
             gpu: Gpu::new(),
             memory: Memory::new(cartridge)?,
@@ -145,6 +789,36 @@ impl GameBoyImpl {
         Ok(gb)
     }
 
+    fn track_branch(&mut self, caller: u16, branch: Option<Branch>) {
+        let registers = self.cpu.registers();
+        match branch {
+            Some(Branch::Call {
+                kind,
+                return_address,
+            }) => self.call_stack.push(Frame {
+                kind,
+                caller,
+                target: registers.pc,
+                bank: self.memory.rom_bank(),
+                return_address,
+                sp: registers.sp,
+            }),
+            Some(Branch::Return) => self.call_stack.on_return(registers.sp),
+            None => {}
+        }
+    }
+
+    fn report_watch_hits(&mut self) {
+        for hit in self.memory.watchpoints.take_hits() {
+            if let Some(hook) = self.debug_hook.as_mut() {
+                hook(&DebugEvent::Watchpoint(hit));
+            }
+            if hit.watchpoint.action == WatchAction::Break {
+                self.watch_breaks.push(hit);
+            }
+        }
+    }
+
     fn serial(&mut self) -> Result<Option<String>> {
         if self.memory.read(SC)? >> 7 == 1 {
             let serial = self.memory.read(SB)?;
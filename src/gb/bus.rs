@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Result};
+
+/// Why the CPU is reading the byte at PC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fetch {
+    Opcode,
+    /// Immediate operands, and the second byte of CB-prefixed instructions.
+    Operand,
+}
+
+/// The address space as seen by the CPU.
+///
+/// `Cpu` is generic over this rather than taking `&mut dyn Bus`, so that the default `Memory`
+/// implementation is statically dispatched and inlined.
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> Result<u8>;
+    fn write(&mut self, addr: u16, val: u8) -> Result<()>;
+
+    /// Reads an instruction byte at PC. Every other CPU read is data.
+    fn fetch(&mut self, addr: u16, _fetch: Fetch) -> Result<u8> {
+        self.read(addr)
+    }
+
+    /// Reads that aren't part of executing an instruction, like tracing or polling IF/IE between
+    /// instructions. Implementations that observe accesses can exclude these.
+    fn peek(&mut self, addr: u16) -> Result<u8> {
+        self.read(addr)
+    }
+
+    /// Called by the CPU with the M-cycles taken by each instruction or interrupt dispatch.
+    fn tick(&mut self, cycles: u8) -> Result<()>;
+
+    /// An M-cycle without a memory access that comes before later accesses of the same
+    /// instruction, like the one before CALL pushes. `tick` still counts it; this is only for
+    /// implementations that observe when each access happens.
+    fn idle(&mut self) {}
+
+    fn write_16(&mut self, addr: u16, val: u16) -> Result<()> {
+        let bytes = val.to_le_bytes();
+        self.write(addr, bytes[0])?;
+        self.write(addr.wrapping_add(1), bytes[1])
+    }
+}
+
+/// 64 KiB of plain RAM with no memory-mapped devices, for running the CPU in isolation.
+pub struct FlatRam {
+    ram: Box<[u8; 0x10000]>,
+    cycles: u64,
+}
+
+impl FlatRam {
+    pub fn new() -> FlatRam {
+        FlatRam {
+            ram: Box::new([0u8; 0x10000]),
+            cycles: 0,
+        }
+    }
+
+    /// Copies `bytes` to `addr` onwards, which must not run past FFFF.
+    pub fn load(&mut self, addr: u16, bytes: &[u8]) -> Result<()> {
+        let start = usize::from(addr);
+        self.ram
+            .get_mut(start..start + bytes.len())
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} bytes at {:#06X} run past the end of RAM",
+                    bytes.len(),
+                    addr
+                )
+            })?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+}
+
+impl Default for FlatRam {
+    fn default() -> Self {
+        FlatRam::new()
+    }
+}
+
+impl Bus for FlatRam {
+    fn read(&mut self, addr: u16) -> Result<u8> {
+        Ok(self.ram[usize::from(addr)])
+    }
+
+    fn write(&mut self, addr: u16, val: u8) -> Result<()> {
+        self.ram[usize::from(addr)] = val;
+        Ok(())
+    }
+
+    fn tick(&mut self, cycles: u8) -> Result<()> {
+        self.cycles += u64::from(cycles);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bus, FlatRam};
+    use crate::gb::cpu::Cpu;
+
+    #[test]
+    fn test_cpu_runs_on_flat_ram() -> anyhow::Result<()> {
+        let mut ram = FlatRam::new();
+        ram.load(0x0100, &[0x3E, 0x42, 0xEA, 0x00, 0xC0])?;
+        let mut cpu = Cpu::new();
+
+        cpu.execute_next_instruction(&mut ram)?;
+        cpu.execute_next_instruction(&mut ram)?;
+
+        assert_eq!(ram.read(0xC000)?, 0x42);
+        assert_eq!(ram.cycles(), 6);
+        assert!(ram.load(0xFFFF, &[0, 0]).is_err());
+        Ok(())
+    }
+}
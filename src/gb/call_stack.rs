@@ -0,0 +1,80 @@
+/// Deepest call stack kept before the oldest frames are dropped, for code that never returns.
+const MAX_DEPTH: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameKind {
+    Call,
+    Rst,
+    Interrupt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    /// Address of the CALL/RST instruction, or of the interrupted instruction.
+    pub caller: u16,
+    pub target: u16,
+    /// The ROM bank mapped at 0x4000 when the call was made, which `target` and `caller` are in
+    /// if they are in the switchable region.
+    pub bank: u16,
+    pub return_address: u16,
+    /// SP after the return address was pushed.
+    pub sp: u16,
+}
+
+pub struct CallStack {
+    frames: Vec<Frame>,
+}
+
+impl CallStack {
+    pub fn new() -> CallStack {
+        CallStack { frames: vec![] }
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn push(&mut self, frame: Frame) {
+        if self.frames.len() == MAX_DEPTH {
+            self.frames.remove(0);
+        }
+        self.frames.push(frame);
+    }
+
+    /// Drops every frame whose return address has been popped off the stack, so that code which
+    /// unwinds the stack by hand doesn't leave stale frames behind.
+    pub fn on_return(&mut self, sp: u16) {
+        while self.frames.last().is_some_and(|frame| frame.sp < sp) {
+            self.frames.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CallStack, Frame, FrameKind};
+
+    fn frame(sp: u16) -> Frame {
+        Frame {
+            kind: FrameKind::Call,
+            caller: 0x0150,
+            target: 0x0200,
+            bank: 1,
+            return_address: 0x0153,
+            sp,
+        }
+    }
+
+    #[test]
+    fn test_on_return_unwinds_popped_frames() {
+        let mut call_stack = CallStack::new();
+        call_stack.push(frame(0xFFFC));
+        call_stack.push(frame(0xFFFA));
+        call_stack.push(frame(0xFFF8));
+
+        call_stack.on_return(0xFFFC);
+
+        assert_eq!(call_stack.frames(), &[frame(0xFFFC)]);
+    }
+}
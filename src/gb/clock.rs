@@ -20,6 +20,11 @@ impl Clock {
         Clock { cycles: 0 }
     }
 
+    /// M-cycles since power on.
+    pub fn cycles(&self) -> usize {
+        self.cycles
+    }
+
     pub fn tick(
         &mut self,
         gpu: &mut Gpu,
@@ -1,11 +1,18 @@
 use crate::gb::bits::{clear_bit, get_bits, get_lsb, set_bit};
+use crate::gb::bus::{Bus, Fetch};
+use crate::gb::call_stack::FrameKind;
+use crate::gb::cpu::opcodes::{Op, OPCODES, PREFIX_OPCODES};
 use crate::gb::memory::map::{IE, IF};
-use crate::gb::memory::Memory;
 use crate::gb::AccessType::{Direct, Indirect};
 use crate::gb::{AccessType, R16_HL};
 use anyhow::anyhow;
 use anyhow::Result;
-use log::info;
+use std::fmt;
+
+pub mod disassembler;
+mod opcodes;
+#[cfg(test)]
+mod single_step_tests;
 
 #[derive(PartialEq, Hash, Eq)]
 pub enum Interrupts {
@@ -25,6 +32,57 @@ pub struct InterruptResult {
 pub struct InstructionResult {
     pub cycles: u8,
     pub is_halt: bool,
+    pub is_lockup: bool,
+    pub branch: Option<Branch>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Branch {
+    Call {
+        kind: FrameKind,
+        return_address: u16,
+    },
+    Return,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Registers {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub ime: bool,
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |bit: u8, name: char| if self.f >> bit & 1 == 1 { name } else { '-' };
+        write!(
+            f,
+            "A:{:02X} F:{:02X} [{}{}{}{}] B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} IME:{}",
+            self.a,
+            self.f,
+            flag(7, 'Z'),
+            flag(6, 'N'),
+            flag(5, 'H'),
+            flag(4, 'C'),
+            self.b,
+            self.c,
+            self.d,
+            self.e,
+            self.h,
+            self.l,
+            self.sp,
+            self.pc,
+            u8::from(self.ime)
+        )
+    }
 }
 
 pub struct Cpu {
@@ -39,6 +97,13 @@ pub struct Cpu {
     l: u8,
     sp: u16,
     pc: u16,
+    branch: Option<Branch>,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Cpu::new()
+    }
 }
 
 impl Cpu {
@@ -55,42 +120,77 @@ impl Cpu {
             l: 0x4D,
             sp: 0xFFFE,
             pc: 0x0100,
+            branch: None,
+        }
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            a: self.a,
+            f: self.f,
+            b: self.b,
+            c: self.c,
+            d: self.d,
+            e: self.e,
+            h: self.h,
+            l: self.l,
+            sp: self.sp,
+            pc: self.pc,
+            ime: self.ime,
         }
     }
 
-    pub fn _log(&mut self, memory: &mut Memory) -> Result<()> {
-        let pc = memory.read(self.pc)?;
-        let pc_1 = memory.read(self.pc + 1)?;
-        let pc_2 = memory.read(self.pc + 2)?;
-        let pc_3 = memory.read(self.pc + 3)?;
-        info!(
-            "A: {:02X?} F: {:02X?} B: {:02X?} C: {:02X?} D: {:02X?} E: {:02X?} H: {:02X?} L: {:02X?} SP: {:04X?} PC: 00:{:04X?} ({:02X?} {:02X?} {:02X?} {:02X?})\n",
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.a = registers.a;
+        self.f = registers.f & 0xF0;
+        self.b = registers.b;
+        self.c = registers.c;
+        self.d = registers.d;
+        self.e = registers.e;
+        self.h = registers.h;
+        self.l = registers.l;
+        self.sp = registers.sp;
+        self.pc = registers.pc;
+        self.ime = registers.ime;
+    }
+
+    /// Formats the registers and the next four bytes at PC as a gameboy-doctor log line.
+    pub fn trace_line<B: Bus>(&self, bus: &mut B) -> Result<String> {
+        let pc = bus.peek(self.pc)?;
+        let pc_1 = bus.peek(self.pc.wrapping_add(1))?;
+        let pc_2 = bus.peek(self.pc.wrapping_add(2))?;
+        let pc_3 = bus.peek(self.pc.wrapping_add(3))?;
+        Ok(format!(
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
             self.a, self.f, self.b, self.c, self.d, self.e, self.h, self.l, self.sp, self.pc, pc, pc_1, pc_2, pc_3
-        );
-        Ok(())
+        ))
     }
 
-    pub fn execute_next_instruction_with_halt_bug(
+    pub fn execute_next_instruction_with_halt_bug<B: Bus>(
         &mut self,
-        memory: &mut Memory,
+        bus: &mut B,
     ) -> Result<InstructionResult> {
-        self.execute_next_instruction_impl(memory, true)
+        self.execute_next_instruction_impl(bus, true)
     }
 
-    pub fn execute_next_instruction(&mut self, memory: &mut Memory) -> Result<InstructionResult> {
-        self.execute_next_instruction_impl(memory, false)
+    pub fn execute_next_instruction<B: Bus>(&mut self, bus: &mut B) -> Result<InstructionResult> {
+        self.execute_next_instruction_impl(bus, false)
     }
 
-    pub fn handle_interrupts(&mut self, memory: &mut Memory) -> Result<InterruptResult> {
-        let if_reg = memory.read(IF)?;
-        let ie_reg = memory.read(IE)?;
+    pub fn handle_interrupts<B: Bus>(&mut self, bus: &mut B) -> Result<InterruptResult> {
+        let if_reg = bus.peek(IF)?;
+        let ie_reg = bus.peek(IE)?;
         let interrupts = if_reg & ie_reg & 0x1F;
 
         Ok(if self.ime && interrupts != 0 {
             self.ime = false;
-            self.push_16(memory, self.pc)?;
+            self.push_16(bus, self.pc)?;
             let lsb = get_lsb(interrupts);
-            memory.write(IF, if_reg & (!lsb))?;
+            bus.write(IF, if_reg & (!lsb))?;
             self.pc = match lsb {
                 1 => Ok(0x40),
                 2 => Ok(0x48),
@@ -99,6 +199,7 @@ impl Cpu {
                 16 => Ok(0x60),
                 _ => Err(anyhow!("Unexpected lsb {}", lsb)),
             }?;
+            bus.tick(5)?;
             InterruptResult {
                 interrupt_requested: true,
                 interrupts_enabled: true,
@@ -113,13 +214,13 @@ impl Cpu {
         })
     }
 
-    fn read_and_increment_pc(&mut self, memory: &mut Memory) -> Result<u8> {
-        let result = memory.read(self.pc)?;
+    fn read_and_increment_pc<B: Bus>(&mut self, bus: &mut B, fetch: Fetch) -> Result<u8> {
+        let result = bus.fetch(self.pc, fetch)?;
         self.pc += 1;
         Ok(result)
     }
 
-    fn read_r8(&mut self, memory: &mut Memory, r: u8) -> Result<(u8, AccessType)> {
+    fn read_r8<B: Bus>(&mut self, bus: &mut B, r: u8) -> Result<(u8, AccessType)> {
         match r {
             0 => Ok((self.b, Direct)),
             1 => Ok((self.c, Direct)),
@@ -127,13 +228,13 @@ impl Cpu {
             3 => Ok((self.e, Direct)),
             4 => Ok((self.h, Direct)),
             5 => Ok((self.l, Direct)),
-            6 => Ok((memory.read(self.read_hl()?)?, Indirect)),
+            6 => Ok((bus.read(self.read_hl()?)?, Indirect)),
             7 => Ok((self.a, Direct)),
             _ => Err(anyhow!("Unknown register {}", r)),
         }
     }
 
-    fn write_r8(&mut self, memory: &mut Memory, r: u8, val: u8) -> anyhow::Result<AccessType> {
+    fn write_r8<B: Bus>(&mut self, bus: &mut B, r: u8, val: u8) -> anyhow::Result<AccessType> {
         match r {
             0 => {
                 self.b = val;
@@ -160,7 +261,7 @@ impl Cpu {
                 Ok(Direct)
             }
             6 => {
-                memory.write(self.read_hl()?, val)?;
+                bus.write(self.read_hl()?, val)?;
                 Ok(Indirect)
             }
             7 => {
@@ -253,14 +354,14 @@ impl Cpu {
         }
     }
 
-    fn read_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        self.read_and_increment_pc(memory)
+    fn read_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        self.read_and_increment_pc(bus, Fetch::Operand)
     }
 
-    fn read_n16(&mut self, memory: &mut Memory) -> Result<u16> {
+    fn read_n16<B: Bus>(&mut self, bus: &mut B) -> Result<u16> {
         Ok(u16::from_le_bytes([
-            self.read_and_increment_pc(memory)?,
-            self.read_and_increment_pc(memory)?,
+            self.read_and_increment_pc(bus, Fetch::Operand)?,
+            self.read_and_increment_pc(bus, Fetch::Operand)?,
         ]))
     }
 
@@ -322,148 +423,143 @@ impl Cpu {
         }
     }
 
-    fn push_8(&mut self, memory: &mut Memory, val: u8) -> Result<()> {
+    fn push_8<B: Bus>(&mut self, bus: &mut B, val: u8) -> Result<()> {
         self.sp -= 1;
-        memory.write(self.sp, val)
+        bus.write(self.sp, val)
     }
 
-    fn push_16(&mut self, memory: &mut Memory, val: u16) -> Result<()> {
+    fn push_16<B: Bus>(&mut self, bus: &mut B, val: u16) -> Result<()> {
+        bus.idle();
         let bytes = val.to_be_bytes();
-        self.push_8(memory, bytes[0])?;
-        self.push_8(memory, bytes[1])
+        self.push_8(bus, bytes[0])?;
+        self.push_8(bus, bytes[1])
     }
 
-    fn pop_8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let res = memory.read(self.sp)?;
+    fn pop_8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let res = bus.read(self.sp)?;
         self.sp += 1;
         Ok(res)
     }
 
-    fn pop_16(&mut self, memory: &mut Memory) -> Result<u16> {
-        Ok(u16::from_le_bytes([
-            self.pop_8(memory)?,
-            self.pop_8(memory)?,
-        ]))
+    fn pop_16<B: Bus>(&mut self, bus: &mut B) -> Result<u16> {
+        Ok(u16::from_le_bytes([self.pop_8(bus)?, self.pop_8(bus)?]))
     }
 
-    fn execute_next_instruction_impl(
+    fn execute_next_instruction_impl<B: Bus>(
         &mut self,
-        memory: &mut Memory,
+        bus: &mut B,
         halt_bug: bool,
     ) -> Result<InstructionResult> {
-        let instruction = self.read_and_increment_pc(memory)?;
+        let instruction = self.read_and_increment_pc(bus, Fetch::Opcode)?;
         if halt_bug {
             self.pc -= 1;
         }
-        let cycles = match instruction {
-            0o0 => self.nop(),
-            0o01 | 0o21 | 0o41 | 0o61 => self.ld_r16_n16(memory, instruction),
-            0o02 | 0o22 | 0o42 | 0o62 => self.ld_ind_r16_a(memory, instruction),
-            0o12 | 0o32 | 0o52 | 0o72 => self.ld_a_ind_r16(memory, instruction),
-            0o10 => self.ld_ind_n16_sp(memory),
-            0o03 | 0o23 | 0o43 | 0o63 => self.inc_r16(instruction),
-            0o13 | 0o33 | 0o53 | 0o73 => self.dec_r16(instruction),
-            0o11 | 0o31 | 0o51 | 0o71 => self.add_hl_r16(instruction),
-            0o04 | 0o14 | 0o24 | 0o34 | 0o44 | 0o54 | 0o64 | 0o74 => {
-                self.inc_r8(memory, instruction)
-            }
-            0o05 | 0o15 | 0o25 | 0o35 | 0o45 | 0o55 | 0o65 | 0o75 => {
-                self.dec_r8(memory, instruction)
-            }
-            0o06 | 0o16 | 0o26 | 0o36 | 0o46 | 0o56 | 0o66 | 0o76 => {
-                self.ld_r8_n8(memory, instruction)
-            }
-            0o07 => self.rlca(),
-            0o17 => self.rrca(),
-            0o27 => self.rla(),
-            0o37 => self.rra(),
-            0o47 => self.daa(),
-            0o57 => self.cpl(),
-            0o67 => self.scf(),
-            0o77 => self.ccf(),
-            0o30 => self.jr_n8(memory),
-            0o40 | 0o50 | 0o60 | 0o70 => self.jr_cond_n8(memory, instruction),
-            0o20 => self.stop(),
-            0o166 => self.halt(),
-            (0o100..=0o177) => self.ld_r8_r8(memory, instruction),
-            0o200..=0o207 => self.add_a_r8(memory, instruction),
-            0o210..=0o217 => self.adc_a_r8(memory, instruction),
-            0o220..=0o227 => self.sub_a_r8(memory, instruction),
-            0o230..=0o237 => self.sbc_a_r8(memory, instruction),
-            0o240..=0o247 => self.and_a_r8(memory, instruction),
-            0o250..=0o257 => self.xor_a_r8(memory, instruction),
-            0o260..=0o267 => self.or_a_r8(memory, instruction),
-            0o270..=0o277 => self.cp_a_r8(memory, instruction),
-            0o306 => self.add_a_n8(memory),
-            0o316 => self.adc_a_n8(memory),
-            0o326 => self.sub_a_n8(memory),
-            0o336 => self.sbc_a_n8(memory),
-            0o346 => self.and_a_n8(memory),
-            0o356 => self.xor_a_n8(memory),
-            0o366 => self.or_a_n8(memory),
-            0o376 => self.cp_a_n8(memory),
-            0o300 | 0o310 | 0o320 | 0o330 => self.ret_cond(memory, instruction),
-            0o311 => self.ret(memory),
-            0o331 => self.reti(memory),
-            0o302 | 0o312 | 0o322 | 0o332 => self.jp_cond_n16(memory, instruction),
-            0o303 => self.jp_n16(memory),
-            0o351 => self.jp_hl(),
-            0o304 | 0o314 | 0o324 | 0o334 => self.call_cond_n16(memory, instruction),
-            0o315 => self.call_n16(memory),
-            0o307 | 0o317 | 0o327 | 0o337 | 0o347 | 0o357 | 0o367 | 0o377 => {
-                self.rst(memory, instruction)
-            }
-            0o301 | 0o321 | 0o341 | 0o361 => self.pop(memory, instruction),
-            0o305 | 0o325 | 0o345 | 0o365 => self.push(memory, instruction),
-            0o313 => self.prefix(memory),
-            0o342 => self.ldh_ind_c_a(memory),
-            0o340 => self.ldh_ind_n8_a(memory),
-            0o352 => self.ld_ind_n16_a(memory),
-            0o362 => self.ldh_a_ind_c(memory),
-            0o360 => self.ldh_a_ind_n8(memory),
-            0o372 => self.ld_a_ind_n16(memory),
-            0o350 => self.add_sp_n8(memory),
-            0o370 => self.ld_hl_sp_plus_n8(memory),
-            0o371 => self.ld_sp_hl(),
-            0o363 => self.di(),
-            0o373 => self.ei(),
-            _ => Err(anyhow!("Unknown instruction {}", instruction)),
+        let op = OPCODES[usize::from(instruction)].op;
+        let cycles = match op {
+            Op::Nop => self.nop(),
+            Op::LdR16N16 => self.ld_r16_n16(bus, instruction),
+            Op::LdIndR16A => self.ld_ind_r16_a(bus, instruction),
+            Op::LdAIndR16 => self.ld_a_ind_r16(bus, instruction),
+            Op::LdIndN16Sp => self.ld_ind_n16_sp(bus),
+            Op::IncR16 => self.inc_r16(instruction),
+            Op::DecR16 => self.dec_r16(instruction),
+            Op::AddHlR16 => self.add_hl_r16(instruction),
+            Op::IncR8 => self.inc_r8(bus, instruction),
+            Op::DecR8 => self.dec_r8(bus, instruction),
+            Op::LdR8N8 => self.ld_r8_n8(bus, instruction),
+            Op::Rlca => self.rlca(),
+            Op::Rrca => self.rrca(),
+            Op::Rla => self.rla(),
+            Op::Rra => self.rra(),
+            Op::Daa => self.daa(),
+            Op::Cpl => self.cpl(),
+            Op::Scf => self.scf(),
+            Op::Ccf => self.ccf(),
+            Op::JrN8 => self.jr_n8(bus),
+            Op::JrCondN8 => self.jr_cond_n8(bus, instruction),
+            Op::Stop => self.stop(),
+            Op::Halt => self.halt(),
+            Op::LdR8R8 => self.ld_r8_r8(bus, instruction),
+            Op::AddAR8 => self.add_a_r8(bus, instruction),
+            Op::AdcAR8 => self.adc_a_r8(bus, instruction),
+            Op::SubAR8 => self.sub_a_r8(bus, instruction),
+            Op::SbcAR8 => self.sbc_a_r8(bus, instruction),
+            Op::AndAR8 => self.and_a_r8(bus, instruction),
+            Op::XorAR8 => self.xor_a_r8(bus, instruction),
+            Op::OrAR8 => self.or_a_r8(bus, instruction),
+            Op::CpAR8 => self.cp_a_r8(bus, instruction),
+            Op::AddAN8 => self.add_a_n8(bus),
+            Op::AdcAN8 => self.adc_a_n8(bus),
+            Op::SubAN8 => self.sub_a_n8(bus),
+            Op::SbcAN8 => self.sbc_a_n8(bus),
+            Op::AndAN8 => self.and_a_n8(bus),
+            Op::XorAN8 => self.xor_a_n8(bus),
+            Op::OrAN8 => self.or_a_n8(bus),
+            Op::CpAN8 => self.cp_a_n8(bus),
+            Op::RetCond => self.ret_cond(bus, instruction),
+            Op::Ret => self.ret(bus),
+            Op::Reti => self.reti(bus),
+            Op::JpCondN16 => self.jp_cond_n16(bus, instruction),
+            Op::JpN16 => self.jp_n16(bus),
+            Op::JpHl => self.jp_hl(),
+            Op::CallCondN16 => self.call_cond_n16(bus, instruction),
+            Op::CallN16 => self.call_n16(bus),
+            Op::Rst => self.rst(bus, instruction),
+            Op::Pop => self.pop(bus, instruction),
+            Op::Push => self.push(bus, instruction),
+            Op::Prefix => self.prefix(bus),
+            Op::LdhIndCA => self.ldh_ind_c_a(bus),
+            Op::LdhIndN8A => self.ldh_ind_n8_a(bus),
+            Op::LdIndN16A => self.ld_ind_n16_a(bus),
+            Op::LdhAIndC => self.ldh_a_ind_c(bus),
+            Op::LdhAIndN8 => self.ldh_a_ind_n8(bus),
+            Op::LdAIndN16 => self.ld_a_ind_n16(bus),
+            Op::AddSpN8 => self.add_sp_n8(bus),
+            Op::LdHlSpPlusN8 => self.ld_hl_sp_plus_n8(bus),
+            Op::LdSpHl => self.ld_sp_hl(),
+            Op::Di => self.di(),
+            Op::Ei => self.ei(),
+            Op::Illegal => self.illegal(),
+            op => Err(anyhow!("{:?} needs the 0xCB prefix", op)),
         }?;
+        bus.tick(cycles)?;
 
         Ok(InstructionResult {
             cycles,
-            is_halt: instruction == 0o166,
+            is_halt: op == Op::Halt,
+            is_lockup: op == Op::Illegal,
+            branch: self.branch.take(),
         })
     }
 
     fn nop(&self) -> Result<u8> {
-        Ok(2)
+        Ok(1)
     }
 
-    fn ld_r16_n16(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
-        let operand = self.read_n16(memory)?;
+    fn ld_r16_n16<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
+        let operand = self.read_n16(bus)?;
         let reg = get_bits(instruction, 5, 4);
         self.write_r16(reg, operand)?;
         Ok(3)
     }
 
-    fn ld_ind_r16_a(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn ld_ind_r16_a<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 5, 4);
         let address = self.r16_mem(reg)?;
-        memory.write(address, self.a)?;
+        bus.write(address, self.a)?;
         Ok(2)
     }
 
-    fn ld_a_ind_r16(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn ld_a_ind_r16<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 5, 4);
         let address = self.r16_mem(reg)?;
-        self.a = memory.read(address)?;
+        self.a = bus.read(address)?;
         Ok(2)
     }
 
-    fn ld_ind_n16_sp(&mut self, memory: &mut Memory) -> Result<u8> {
-        let address = self.read_n16(memory)?;
-        memory.write_16(address, self.sp)?;
+    fn ld_ind_n16_sp<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let address = self.read_n16(bus)?;
+        bus.write_16(address, self.sp)?;
         Ok(5)
     }
 
@@ -491,11 +587,11 @@ impl Cpu {
         Ok(2)
     }
 
-    fn inc_r8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn inc_r8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 5, 3);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         let result = operand.wrapping_add(1);
-        self.write_r8(memory, reg, result)?;
+        self.write_r8(bus, reg, result)?;
         self.set_z(result == 0);
         self.set_n(false);
         self.set_h(is_add_half_carry_8(operand, 1));
@@ -505,11 +601,11 @@ impl Cpu {
         })
     }
 
-    fn dec_r8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn dec_r8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 5, 3);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         let result = operand.wrapping_sub(1);
-        self.write_r8(memory, reg, result)?;
+        self.write_r8(bus, reg, result)?;
         self.set_z(result == 0);
         self.set_n(true);
         self.set_h(is_sub_half_carry_8(operand, 1));
@@ -519,10 +615,10 @@ impl Cpu {
         })
     }
 
-    fn ld_r8_n8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn ld_r8_n8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 5, 3);
-        let operand = self.read_n8(memory)?;
-        Ok(match self.write_r8(memory, reg, operand)? {
+        let operand = self.read_n8(bus)?;
+        Ok(match self.write_r8(bus, reg, operand)? {
             Direct => 2,
             Indirect => 3,
         })
@@ -611,15 +707,15 @@ impl Cpu {
         Ok(1)
     }
 
-    fn jr_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let offset = i16::from(self.read_n8(memory)? as i8);
+    fn jr_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let offset = i16::from(self.read_n8(bus)? as i8);
         self.pc = self.pc.wrapping_add_signed(offset);
         Ok(3)
     }
 
-    fn jr_cond_n8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn jr_cond_n8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let cond = get_bits(instruction, 4, 3);
-        let offset = i16::from(self.read_n8(memory)? as i8);
+        let offset = i16::from(self.read_n8(bus)? as i8);
         Ok(if self.read_cond(cond)? {
             self.pc = self.pc.wrapping_add_signed(offset);
             3
@@ -636,19 +732,23 @@ impl Cpu {
         Ok(1)
     }
 
-    fn ld_r8_r8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn illegal(&mut self) -> Result<u8> {
+        Ok(1)
+    }
+
+    fn ld_r8_r8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let source = get_bits(instruction, 2, 0);
         let dest = get_bits(instruction, 5, 3);
-        let (value, access_type) = self.read_r8(memory, source)?;
-        Ok(match self.write_r8(memory, dest, value)? + access_type {
+        let (value, access_type) = self.read_r8(bus, source)?;
+        Ok(match self.write_r8(bus, dest, value)? + access_type {
             Direct => 1,
             Indirect => 2,
         })
     }
 
-    fn add_a_r8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn add_a_r8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         self.a = self.add_and_set_flags_no_carry(self.a, operand);
         Ok(match access_type {
             Direct => 1,
@@ -656,9 +756,9 @@ impl Cpu {
         })
     }
 
-    fn adc_a_r8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn adc_a_r8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         self.a = self.add_and_set_flags_with_carry(self.a, operand);
         Ok(match access_type {
             Direct => 1,
@@ -666,27 +766,27 @@ impl Cpu {
         })
     }
 
-    fn sub_a_r8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn sub_a_r8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         self.a = self.sub_and_set_flags_no_carry(self.a, operand);
         Ok(match access_type {
             Direct => 1,
             Indirect => 2,
         })
     }
-    fn sbc_a_r8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn sbc_a_r8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         self.a = self.sub_and_set_flags_with_carry(self.a, operand);
         Ok(match access_type {
             Direct => 1,
             Indirect => 2,
         })
     }
-    fn and_a_r8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn and_a_r8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         self.a &= operand;
         self.set_z(self.a == 0);
         self.set_n(false);
@@ -697,9 +797,9 @@ impl Cpu {
             Indirect => 2,
         })
     }
-    fn xor_a_r8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn xor_a_r8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         self.a ^= operand;
         self.set_z(self.a == 0);
         self.set_n(false);
@@ -710,9 +810,9 @@ impl Cpu {
             Indirect => 2,
         })
     }
-    fn or_a_r8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn or_a_r8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         self.a |= operand;
         self.set_z(self.a == 0);
         self.set_n(false);
@@ -723,9 +823,9 @@ impl Cpu {
             Indirect => 2,
         })
     }
-    fn cp_a_r8(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn cp_a_r8<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         self.sub_and_set_flags_no_carry(self.a, operand);
         Ok(match access_type {
             Direct => 1,
@@ -733,30 +833,30 @@ impl Cpu {
         })
     }
 
-    fn add_a_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let operand = self.read_n8(memory)?;
+    fn add_a_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let operand = self.read_n8(bus)?;
         self.a = self.add_and_set_flags_no_carry(self.a, operand);
         Ok(2)
     }
 
-    fn adc_a_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let operand = self.read_n8(memory)?;
+    fn adc_a_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let operand = self.read_n8(bus)?;
         self.a = self.add_and_set_flags_with_carry(self.a, operand);
         Ok(2)
     }
 
-    fn sub_a_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let operand = self.read_n8(memory)?;
+    fn sub_a_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let operand = self.read_n8(bus)?;
         self.a = self.sub_and_set_flags_no_carry(self.a, operand);
         Ok(2)
     }
-    fn sbc_a_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let operand = self.read_n8(memory)?;
+    fn sbc_a_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let operand = self.read_n8(bus)?;
         self.a = self.sub_and_set_flags_with_carry(self.a, operand);
         Ok(2)
     }
-    fn and_a_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let operand = self.read_n8(memory)?;
+    fn and_a_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let operand = self.read_n8(bus)?;
         self.a &= operand;
         self.set_z(self.a == 0);
         self.set_n(false);
@@ -764,8 +864,8 @@ impl Cpu {
         self.set_c(false);
         Ok(2)
     }
-    fn xor_a_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let operand = self.read_n8(memory)?;
+    fn xor_a_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let operand = self.read_n8(bus)?;
         self.a ^= operand;
         self.set_z(self.a == 0);
         self.set_n(false);
@@ -773,8 +873,8 @@ impl Cpu {
         self.set_c(false);
         Ok(2)
     }
-    fn or_a_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let operand = self.read_n8(memory)?;
+    fn or_a_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let operand = self.read_n8(bus)?;
         self.a |= operand;
         self.set_z(self.a == 0);
         self.set_n(false);
@@ -782,38 +882,43 @@ impl Cpu {
         self.set_c(false);
         Ok(2)
     }
-    fn cp_a_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let operand = self.read_n8(memory)?;
+    fn cp_a_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let operand = self.read_n8(bus)?;
         self.sub_and_set_flags(self.a, operand, None);
         Ok(2)
     }
 
-    fn ret_cond(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn ret_cond<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let cond = get_bits(instruction, 4, 3);
         let should_ret = self.read_cond(cond)?;
+        // Checking the condition takes a cycle of its own.
+        bus.idle();
         Ok(if should_ret {
-            let dest = self.pop_16(memory)?;
+            let dest = self.pop_16(bus)?;
             self.pc = dest;
+            self.branch = Some(Branch::Return);
             5
         } else {
             2
         })
     }
 
-    fn ret(&mut self, memory: &mut Memory) -> Result<u8> {
-        self.pc = self.pop_16(memory)?;
+    fn ret<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        self.pc = self.pop_16(bus)?;
+        self.branch = Some(Branch::Return);
         Ok(4)
     }
 
-    fn reti(&mut self, memory: &mut Memory) -> Result<u8> {
-        self.pc = self.pop_16(memory)?;
+    fn reti<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        self.pc = self.pop_16(bus)?;
+        self.branch = Some(Branch::Return);
         self.ime = true;
         Ok(4)
     }
 
-    fn jp_cond_n16(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn jp_cond_n16<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let cond = get_bits(instruction, 4, 3);
-        let dest = self.read_n16(memory)?;
+        let dest = self.read_n16(bus)?;
         Ok(if self.read_cond(cond)? {
             self.pc = dest;
             4
@@ -822,8 +927,8 @@ impl Cpu {
         })
     }
 
-    fn jp_n16(&mut self, memory: &mut Memory) -> Result<u8> {
-        self.pc = self.read_n16(memory)?;
+    fn jp_n16<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        self.pc = self.read_n16(bus)?;
         Ok(4)
     }
 
@@ -832,11 +937,15 @@ impl Cpu {
         Ok(1)
     }
 
-    fn call_cond_n16(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn call_cond_n16<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let cond = get_bits(instruction, 4, 3);
-        let addr = self.read_n16(memory)?;
+        let addr = self.read_n16(bus)?;
         Ok(if self.read_cond(cond)? {
-            self.push_16(memory, self.pc)?;
+            self.push_16(bus, self.pc)?;
+            self.branch = Some(Branch::Call {
+                kind: FrameKind::Call,
+                return_address: self.pc,
+            });
             self.pc = addr;
             6
         } else {
@@ -844,76 +953,84 @@ impl Cpu {
         })
     }
 
-    fn call_n16(&mut self, memory: &mut Memory) -> Result<u8> {
-        let addr = self.read_n16(memory)?;
-        self.push_16(memory, self.pc)?;
+    fn call_n16<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let addr = self.read_n16(bus)?;
+        self.push_16(bus, self.pc)?;
+        self.branch = Some(Branch::Call {
+            kind: FrameKind::Call,
+            return_address: self.pc,
+        });
         self.pc = addr;
         Ok(6)
     }
 
-    fn rst(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn rst<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let operand = get_bits(instruction, 5, 3);
         let addr = u16::from(operand) * 8;
-        self.push_16(memory, self.pc)?;
+        self.push_16(bus, self.pc)?;
+        self.branch = Some(Branch::Call {
+            kind: FrameKind::Rst,
+            return_address: self.pc,
+        });
         self.pc = addr;
         Ok(4)
     }
 
-    fn pop(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
-        let value = self.pop_16(memory)?;
+    fn pop<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
+        let value = self.pop_16(bus)?;
         let reg = get_bits(instruction, 5, 4);
         self.write_r16_stk(reg, value)?;
         Ok(3)
     }
 
-    fn push(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn push<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 5, 4);
-        self.push_16(memory, self.read_r16_stk(reg)?)?;
+        self.push_16(bus, self.read_r16_stk(reg)?)?;
         Ok(4)
     }
 
-    fn ldh_ind_c_a(&mut self, memory: &mut Memory) -> Result<u8> {
-        memory.write(0xFF00 + u16::from(self.c), self.a)?;
+    fn ldh_ind_c_a<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        bus.write(0xFF00 + u16::from(self.c), self.a)?;
         Ok(2)
     }
 
-    fn ldh_ind_n8_a(&mut self, memory: &mut Memory) -> Result<u8> {
-        let address = 0xFF00 + u16::from(self.read_n8(memory)?);
-        memory.write(address, self.a)?;
+    fn ldh_ind_n8_a<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let address = 0xFF00 + u16::from(self.read_n8(bus)?);
+        bus.write(address, self.a)?;
         Ok(3)
     }
 
-    fn ld_ind_n16_a(&mut self, memory: &mut Memory) -> Result<u8> {
-        let address = self.read_n16(memory)?;
-        memory.write(address, self.a)?;
+    fn ld_ind_n16_a<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let address = self.read_n16(bus)?;
+        bus.write(address, self.a)?;
         Ok(4)
     }
 
-    fn ldh_a_ind_c(&mut self, memory: &mut Memory) -> Result<u8> {
-        self.a = memory.read(0xFF00 + u16::from(self.c))?;
+    fn ldh_a_ind_c<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        self.a = bus.read(0xFF00 + u16::from(self.c))?;
         Ok(2)
     }
 
-    fn ldh_a_ind_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let address = 0xFF00 + u16::from(self.read_n8(memory)?);
-        self.a = memory.read(address)?;
+    fn ldh_a_ind_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let address = 0xFF00 + u16::from(self.read_n8(bus)?);
+        self.a = bus.read(address)?;
         Ok(3)
     }
 
-    fn ld_a_ind_n16(&mut self, memory: &mut Memory) -> Result<u8> {
-        let address = self.read_n16(memory)?;
-        self.a = memory.read(address)?;
+    fn ld_a_ind_n16<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let address = self.read_n16(bus)?;
+        self.a = bus.read(address)?;
         Ok(4)
     }
 
-    fn add_sp_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let operand = self.read_n8(memory)? as i8;
+    fn add_sp_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let operand = self.read_n8(bus)? as i8;
         self.sp = self.add_signed_and_set_flags(self.sp, operand);
         Ok(4)
     }
 
-    fn ld_hl_sp_plus_n8(&mut self, memory: &mut Memory) -> Result<u8> {
-        let operand = self.read_n8(memory)? as i8;
+    fn ld_hl_sp_plus_n8<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let operand = self.read_n8(bus)? as i8;
         let result = self.add_signed_and_set_flags(self.sp, operand);
         self.write_hl(result)?;
         Ok(3)
@@ -934,130 +1051,130 @@ impl Cpu {
         Ok(1)
     }
 
-    fn rlc(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn rlc<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         let (result, carry) = rotate_left(operand);
         self.set_z(result == 0);
         self.set_n(false);
         self.set_h(false);
         self.set_c(carry);
-        self.write_r8(memory, reg, result)?;
+        self.write_r8(bus, reg, result)?;
         Ok(match access_type {
             Direct => 2,
             Indirect => 4,
         })
     }
 
-    fn rrc(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn rrc<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         let (result, carry) = rotate_right(operand);
         self.set_z(result == 0);
         self.set_n(false);
         self.set_h(false);
         self.set_c(carry);
-        self.write_r8(memory, reg, result)?;
+        self.write_r8(bus, reg, result)?;
         Ok(match access_type {
             Direct => 2,
             Indirect => 4,
         })
     }
 
-    fn rl(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn rl<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         let (result, carry) = rotate_left_with_carry(operand, self.get_c());
         self.set_z(result == 0);
         self.set_n(false);
         self.set_h(false);
         self.set_c(carry);
-        self.write_r8(memory, reg, result)?;
+        self.write_r8(bus, reg, result)?;
         Ok(match access_type {
             Direct => 2,
             Indirect => 4,
         })
     }
 
-    fn rr(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn rr<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         let (result, carry) = rotate_right_with_carry(operand, self.get_c());
         self.set_z(result == 0);
         self.set_n(false);
         self.set_h(false);
         self.set_c(carry);
-        self.write_r8(memory, reg, result)?;
+        self.write_r8(bus, reg, result)?;
         Ok(match access_type {
             Direct => 2,
             Indirect => 4,
         })
     }
 
-    fn sla(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn sla<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         let (result, carry) = shift_left(operand);
         self.set_z(result == 0);
         self.set_n(false);
         self.set_h(false);
         self.set_c(carry);
-        self.write_r8(memory, reg, result)?;
+        self.write_r8(bus, reg, result)?;
         Ok(match access_type {
             Direct => 2,
             Indirect => 4,
         })
     }
 
-    fn sra(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn sra<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         let (result, carry) = shift_right_arithmetic(operand);
         self.set_z(result == 0);
         self.set_n(false);
         self.set_h(false);
         self.set_c(carry);
-        self.write_r8(memory, reg, result)?;
+        self.write_r8(bus, reg, result)?;
         Ok(match access_type {
             Direct => 2,
             Indirect => 4,
         })
     }
 
-    fn swap(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn swap<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         let result = operand.wrapping_shr(4) + operand.wrapping_shl(4);
         self.set_z(result == 0);
         self.set_n(false);
         self.set_h(false);
         self.set_c(false);
-        self.write_r8(memory, reg, result)?;
+        self.write_r8(bus, reg, result)?;
         Ok(match access_type {
             Direct => 2,
             Indirect => 4,
         })
     }
 
-    fn srl(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn srl<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         let (result, carry) = shift_right_logical(operand);
         self.set_z(result == 0);
         self.set_n(false);
         self.set_h(false);
         self.set_c(carry);
-        self.write_r8(memory, reg, result)?;
+        self.write_r8(bus, reg, result)?;
         Ok(match access_type {
             Direct => 2,
             Indirect => 4,
         })
     }
 
-    fn bit(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn bit<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
         let bit = get_bits(instruction, 5, 3);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
         self.set_z(get_bits(operand, bit, bit) == 0);
         self.set_n(false);
         self.set_h(true);
@@ -1067,42 +1184,43 @@ impl Cpu {
         })
     }
 
-    fn res(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn res<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
         let bit = get_bits(instruction, 5, 3);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
-        self.write_r8(memory, reg, clear_bit(operand, bit))?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
+        self.write_r8(bus, reg, clear_bit(operand, bit))?;
         Ok(match access_type {
             Direct => 2,
             Indirect => 4,
         })
     }
 
-    fn set(&mut self, memory: &mut Memory, instruction: u8) -> Result<u8> {
+    fn set<B: Bus>(&mut self, bus: &mut B, instruction: u8) -> Result<u8> {
         let reg = get_bits(instruction, 2, 0);
         let bit = get_bits(instruction, 5, 3);
-        let (operand, access_type) = self.read_r8(memory, reg)?;
-        self.write_r8(memory, reg, set_bit(operand, bit))?;
+        let (operand, access_type) = self.read_r8(bus, reg)?;
+        self.write_r8(bus, reg, set_bit(operand, bit))?;
         Ok(match access_type {
             Direct => 2,
             Indirect => 4,
         })
     }
 
-    fn prefix(&mut self, memory: &mut Memory) -> Result<u8> {
-        let instruction = self.read_and_increment_pc(memory)?;
-        match instruction {
-            (0o00..=0o07) => self.rlc(memory, instruction),
-            (0o10..=0o17) => self.rrc(memory, instruction),
-            (0o20..=0o27) => self.rl(memory, instruction),
-            (0o30..=0o37) => self.rr(memory, instruction),
-            (0o40..=0o47) => self.sla(memory, instruction),
-            (0o50..=0o57) => self.sra(memory, instruction),
-            (0o60..=0o67) => self.swap(memory, instruction),
-            (0o70..=0o77) => self.srl(memory, instruction),
-            (0o100..=0o177) => self.bit(memory, instruction),
-            (0o200..=0o277) => self.res(memory, instruction),
-            (0o300..=0o377) => self.set(memory, instruction),
+    fn prefix<B: Bus>(&mut self, bus: &mut B) -> Result<u8> {
+        let instruction = self.read_and_increment_pc(bus, Fetch::Operand)?;
+        match PREFIX_OPCODES[usize::from(instruction)].op {
+            Op::Rlc => self.rlc(bus, instruction),
+            Op::Rrc => self.rrc(bus, instruction),
+            Op::Rl => self.rl(bus, instruction),
+            Op::Rr => self.rr(bus, instruction),
+            Op::Sla => self.sla(bus, instruction),
+            Op::Sra => self.sra(bus, instruction),
+            Op::Swap => self.swap(bus, instruction),
+            Op::Srl => self.srl(bus, instruction),
+            Op::Bit => self.bit(bus, instruction),
+            Op::Res => self.res(bus, instruction),
+            Op::Set => self.set(bus, instruction),
+            op => Err(anyhow!("{:?} is not a prefixed instruction", op)),
         }
     }
 
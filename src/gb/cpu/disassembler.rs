@@ -0,0 +1,212 @@
+use crate::gb::bits::get_bits;
+use crate::gb::cpu::opcodes::{Op, Operand, OPCODES, PREFIX_OPCODES};
+use anyhow::Result;
+
+/// Register operand names, indexed the same way as `Cpu::read_r8`/`Cpu::write_r8`.
+const R8: [&str; 8] = ["b", "c", "d", "e", "h", "l", "[hl]", "a"];
+/// Indexed like `Cpu::read_r16`.
+const R16: [&str; 4] = ["bc", "de", "hl", "sp"];
+/// Indexed like `Cpu::read_r16_stk`.
+const R16_STK: [&str; 4] = ["bc", "de", "hl", "af"];
+/// Indexed like `Cpu::r16_mem`.
+const R16_MEM: [&str; 4] = ["[bc]", "[de]", "[hl+]", "[hl-]"];
+/// Indexed like `Cpu::read_cond`.
+const COND: [&str; 4] = ["nz", "z", "nc", "c"];
+
+#[derive(Debug, PartialEq)]
+pub struct Disassembly {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+struct Operands<F: FnMut(u16) -> Result<u8>, L: Fn(u16) -> Option<String>> {
+    read: F,
+    label: L,
+    address: u16,
+    bytes: Vec<u8>,
+}
+
+impl<F: FnMut(u16) -> Result<u8>, L: Fn(u16) -> Option<String>> Operands<F, L> {
+    fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.bytes.len() as u16)
+    }
+
+    fn n8(&mut self) -> Result<u8> {
+        let address = self.next_address();
+        let byte = (self.read)(address)?;
+        self.bytes.push(byte);
+        Ok(byte)
+    }
+
+    fn n16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes([self.n8()?, self.n8()?]))
+    }
+
+    fn e8(&mut self) -> Result<String> {
+        let offset = self.n8()? as i8;
+        Ok(if offset < 0 {
+            format!("-${:02X}", offset.unsigned_abs())
+        } else {
+            format!("+${:02X}", offset)
+        })
+    }
+
+    /// A 16-bit address operand, as a label if there is one.
+    fn a16(&mut self) -> Result<String> {
+        let address = self.n16()?;
+        Ok(self.format_address(address))
+    }
+
+    fn jr_target(&mut self) -> Result<String> {
+        let offset = i16::from(self.n8()? as i8);
+        let address = self.next_address().wrapping_add_signed(offset);
+        Ok(self.format_address(address))
+    }
+
+    fn format(&mut self, operand: Operand, instruction: u8) -> Result<String> {
+        let r16 = usize::from(get_bits(instruction, 5, 4));
+        Ok(match operand {
+            Operand::Fixed(text) => text.to_string(),
+            Operand::R8Dest => R8[usize::from(get_bits(instruction, 5, 3))].to_string(),
+            Operand::R8Source => R8[usize::from(get_bits(instruction, 2, 0))].to_string(),
+            Operand::R16 => R16[r16].to_string(),
+            Operand::R16Stk => R16_STK[r16].to_string(),
+            Operand::R16Mem => R16_MEM[r16].to_string(),
+            Operand::Cond => COND[usize::from(get_bits(instruction, 4, 3))].to_string(),
+            Operand::Bit => get_bits(instruction, 5, 3).to_string(),
+            Operand::Vector => format!("${:02X}", get_bits(instruction, 5, 3) * 8),
+            Operand::Opcode => format!("${:02X}", instruction),
+            Operand::N8 => format!("${:02X}", self.n8()?),
+            Operand::N16 => format!("${:04X}", self.n16()?),
+            Operand::A16 => self.a16()?,
+            Operand::IndA16 => format!("[{}]", self.a16()?),
+            Operand::HighN8 => format!("[$FF{:02X}]", self.n8()?),
+            Operand::Relative => self.jr_target()?,
+            Operand::E8 => self.e8()?.trim_start_matches('+').to_string(),
+            Operand::SpE8 => format!("sp{}", self.e8()?),
+        })
+    }
+
+    fn format_address(&self, address: u16) -> String {
+        (self.label)(address).unwrap_or_else(|| format!("${:04X}", address))
+    }
+}
+
+/// Decodes the instruction at `address` into RGBDS syntax, reading bytes through `read`. Jump,
+/// call and memory operands are replaced by `label(address)` when it returns one.
+pub fn disassemble_with_labels(
+    read: impl FnMut(u16) -> Result<u8>,
+    address: u16,
+    label: impl Fn(u16) -> Option<String>,
+) -> Result<Disassembly> {
+    let mut operands = Operands {
+        read,
+        label,
+        address,
+        bytes: Vec::with_capacity(3),
+    };
+    let mut instruction = operands.n8()?;
+    let mut opcode = &OPCODES[usize::from(instruction)];
+    if opcode.op == Op::Prefix {
+        instruction = operands.n8()?;
+        opcode = &PREFIX_OPCODES[usize::from(instruction)];
+    }
+    let mut text = opcode.mnemonic.to_string();
+    for (index, operand) in opcode.operands.iter().enumerate() {
+        text.push_str(if index == 0 { " " } else { ", " });
+        text.push_str(&operands.format(*operand, instruction)?);
+    }
+    // Bytes the instruction skips without using, like STOP's.
+    while operands.bytes.len() < usize::from(opcode.length) {
+        operands.n8()?;
+    }
+
+    Ok(Disassembly {
+        address,
+        bytes: operands.bytes,
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disassemble_with_labels;
+    use crate::gb::cpu::opcodes::{OPCODES, PREFIX_OPCODES};
+
+    fn text(bytes: &[u8], address: u16) -> (String, usize) {
+        let disassembly = disassemble_with_labels(
+            |addr| Ok(bytes[usize::from(addr.wrapping_sub(address))]),
+            address,
+            |_| None,
+        )
+        .unwrap();
+        (disassembly.text, disassembly.bytes.len())
+    }
+
+    #[test]
+    fn test_immediates() {
+        assert_eq!(text(&[0x31, 0xFE, 0xFF], 0), ("ld sp, $FFFE".into(), 3));
+        assert_eq!(text(&[0x3E, 0x42], 0), ("ld a, $42".into(), 2));
+        assert_eq!(text(&[0xE0, 0x44], 0), ("ldh [$FF44], a".into(), 2));
+        assert_eq!(text(&[0xFE, 0x90], 0), ("cp a, $90".into(), 2));
+        assert_eq!(text(&[0xF8, 0xFD], 0), ("ld hl, sp-$03".into(), 2));
+        assert_eq!(text(&[0xE8, 0x05], 0), ("add sp, $05".into(), 2));
+    }
+
+    #[test]
+    fn test_relative_jumps() {
+        assert_eq!(text(&[0x18, 0xFE], 0x0150), ("jr $0150".into(), 2));
+        assert_eq!(text(&[0x20, 0x05], 0x0150), ("jr nz, $0157".into(), 2));
+    }
+
+    #[test]
+    fn test_registers() {
+        assert_eq!(text(&[0x2A], 0), ("ld a, [hl+]".into(), 1));
+        assert_eq!(text(&[0x70], 0), ("ld [hl], b".into(), 1));
+        assert_eq!(text(&[0xF5], 0), ("push af".into(), 1));
+        assert_eq!(text(&[0xFF], 0), ("rst $38".into(), 1));
+        assert_eq!(text(&[0xD3], 0), ("db $D3".into(), 1));
+    }
+
+    #[test]
+    fn test_labels() {
+        let bytes = [0xCD, 0x50, 0x01, 0xFA, 0x00, 0xC0, 0x18, 0xF8];
+        let label = |addr| match addr {
+            0x0150 => Some("Main".to_string()),
+            0xC000 => Some("wCounter".to_string()),
+            _ => None,
+        };
+        let text = |address: u16| {
+            disassemble_with_labels(|addr| Ok(bytes[usize::from(addr)]), address, label)
+                .unwrap()
+                .text
+        };
+        assert_eq!(text(0), "call Main");
+        assert_eq!(text(3), "ld a, [wCounter]");
+        assert_eq!(text(6), "jr $0000");
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(text(&[0xCB, 0x37], 0), ("swap a".into(), 2));
+        assert_eq!(text(&[0xCB, 0x7E], 0), ("bit 7, [hl]".into(), 2));
+        assert_eq!(text(&[0xCB, 0x80], 0), ("res 0, b".into(), 2));
+    }
+
+    #[test]
+    fn test_lengths_match_table() {
+        for instruction in 0..=0xFF {
+            let (_, length) = text(&[instruction, 0, 0], 0);
+            assert_eq!(
+                length,
+                usize::from(OPCODES[usize::from(instruction)].length)
+            );
+            let (_, length) = text(&[0xCB, instruction], 0);
+            assert_eq!(
+                length,
+                usize::from(PREFIX_OPCODES[usize::from(instruction)].length)
+            );
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! The instruction set as one table, which `Cpu` dispatches on and the disassembler formats from.
+
+/// What an opcode does, one variant per `Cpu` handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Nop,
+    LdR16N16,
+    LdIndR16A,
+    LdAIndR16,
+    LdIndN16Sp,
+    IncR16,
+    DecR16,
+    AddHlR16,
+    IncR8,
+    DecR8,
+    LdR8N8,
+    Rlca,
+    Rrca,
+    Rla,
+    Rra,
+    Daa,
+    Cpl,
+    Scf,
+    Ccf,
+    JrN8,
+    JrCondN8,
+    Stop,
+    Halt,
+    LdR8R8,
+    AddAR8,
+    AdcAR8,
+    SubAR8,
+    SbcAR8,
+    AndAR8,
+    XorAR8,
+    OrAR8,
+    CpAR8,
+    AddAN8,
+    AdcAN8,
+    SubAN8,
+    SbcAN8,
+    AndAN8,
+    XorAN8,
+    OrAN8,
+    CpAN8,
+    RetCond,
+    Ret,
+    Reti,
+    JpCondN16,
+    JpN16,
+    JpHl,
+    CallCondN16,
+    CallN16,
+    Rst,
+    Pop,
+    Push,
+    /// Followed by an opcode from `PREFIX_OPCODES`.
+    Prefix,
+    LdhIndCA,
+    LdhIndN8A,
+    LdIndN16A,
+    LdhAIndC,
+    LdhAIndN8,
+    LdAIndN16,
+    AddSpN8,
+    LdHlSpPlusN8,
+    LdSpHl,
+    Di,
+    Ei,
+    /// Locks up the CPU.
+    Illegal,
+    Rlc,
+    Rrc,
+    Rl,
+    Rr,
+    Sla,
+    Sra,
+    Swap,
+    Srl,
+    Bit,
+    Res,
+    Set,
+}
+
+/// How an operand is encoded, either in the opcode's bits or in the bytes after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// A register or memory operand that is always the same, e.g. `a` or `[c]`.
+    Fixed(&'static str),
+    /// An 8-bit register in bits 5-3, indexed like `Cpu::read_r8`.
+    R8Dest,
+    /// An 8-bit register in bits 2-0.
+    R8Source,
+    /// Bits 5-4, indexed like `Cpu::read_r16`.
+    R16,
+    /// Bits 5-4, indexed like `Cpu::read_r16_stk`.
+    R16Stk,
+    /// Bits 5-4, indexed like `Cpu::r16_mem`.
+    R16Mem,
+    /// Bits 4-3, indexed like `Cpu::read_cond`.
+    Cond,
+    /// The bit number in bits 5-3 of a prefixed opcode.
+    Bit,
+    /// The RST vector, bits 5-3 times 8.
+    Vector,
+    /// The opcode itself, for illegal ones.
+    Opcode,
+    N8,
+    N16,
+    /// A 16-bit jump or call target.
+    A16,
+    /// `[a16]`.
+    IndA16,
+    /// `[$FF00 + n8]`.
+    HighN8,
+    /// A signed offset from the next instruction.
+    Relative,
+    /// A signed 8-bit immediate.
+    E8,
+    /// `sp` plus a signed 8-bit immediate.
+    SpE8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opcode {
+    pub op: Op,
+    /// In RGBDS syntax.
+    pub mnemonic: &'static str,
+    pub operands: &'static [Operand],
+    /// In bytes, including the opcode and for prefixed opcodes the prefix.
+    pub length: u8,
+}
+
+const fn opcode(
+    op: Op,
+    mnemonic: &'static str,
+    operands: &'static [Operand],
+    length: u8,
+) -> Opcode {
+    Opcode {
+        op,
+        mnemonic,
+        operands,
+        length,
+    }
+}
+
+const A: Operand = Operand::Fixed("a");
+const HL: Operand = Operand::Fixed("hl");
+const SP: Operand = Operand::Fixed("sp");
+const IND_C: Operand = Operand::Fixed("[c]");
+
+use Operand::{Bit, Cond, HighN8, IndA16, R16Mem, R16Stk, R8Dest, R8Source, Relative, Vector};
+use Operand::{SpE8, A16, E8, N16, N8, R16};
+
+const fn decode(instruction: u8) -> Opcode {
+    match instruction {
+        0o0 => opcode(Op::Nop, "nop", &[], 1),
+        0o01 | 0o21 | 0o41 | 0o61 => opcode(Op::LdR16N16, "ld", &[R16, N16], 3),
+        0o02 | 0o22 | 0o42 | 0o62 => opcode(Op::LdIndR16A, "ld", &[R16Mem, A], 1),
+        0o12 | 0o32 | 0o52 | 0o72 => opcode(Op::LdAIndR16, "ld", &[A, R16Mem], 1),
+        0o10 => opcode(Op::LdIndN16Sp, "ld", &[IndA16, SP], 3),
+        0o03 | 0o23 | 0o43 | 0o63 => opcode(Op::IncR16, "inc", &[R16], 1),
+        0o13 | 0o33 | 0o53 | 0o73 => opcode(Op::DecR16, "dec", &[R16], 1),
+        0o11 | 0o31 | 0o51 | 0o71 => opcode(Op::AddHlR16, "add", &[HL, R16], 1),
+        0o04 | 0o14 | 0o24 | 0o34 | 0o44 | 0o54 | 0o64 | 0o74 => {
+            opcode(Op::IncR8, "inc", &[R8Dest], 1)
+        }
+        0o05 | 0o15 | 0o25 | 0o35 | 0o45 | 0o55 | 0o65 | 0o75 => {
+            opcode(Op::DecR8, "dec", &[R8Dest], 1)
+        }
+        0o06 | 0o16 | 0o26 | 0o36 | 0o46 | 0o56 | 0o66 | 0o76 => {
+            opcode(Op::LdR8N8, "ld", &[R8Dest, N8], 2)
+        }
+        0o07 => opcode(Op::Rlca, "rlca", &[], 1),
+        0o17 => opcode(Op::Rrca, "rrca", &[], 1),
+        0o27 => opcode(Op::Rla, "rla", &[], 1),
+        0o37 => opcode(Op::Rra, "rra", &[], 1),
+        0o47 => opcode(Op::Daa, "daa", &[], 1),
+        0o57 => opcode(Op::Cpl, "cpl", &[], 1),
+        0o67 => opcode(Op::Scf, "scf", &[], 1),
+        0o77 => opcode(Op::Ccf, "ccf", &[], 1),
+        0o30 => opcode(Op::JrN8, "jr", &[Relative], 2),
+        0o40 | 0o50 | 0o60 | 0o70 => opcode(Op::JrCondN8, "jr", &[Cond, Relative], 2),
+        // STOP is followed by a byte that is skipped.
+        0o20 => opcode(Op::Stop, "stop", &[], 2),
+        0o166 => opcode(Op::Halt, "halt", &[], 1),
+        0o100..=0o177 => opcode(Op::LdR8R8, "ld", &[R8Dest, R8Source], 1),
+        0o200..=0o207 => opcode(Op::AddAR8, "add", &[A, R8Source], 1),
+        0o210..=0o217 => opcode(Op::AdcAR8, "adc", &[A, R8Source], 1),
+        0o220..=0o227 => opcode(Op::SubAR8, "sub", &[A, R8Source], 1),
+        0o230..=0o237 => opcode(Op::SbcAR8, "sbc", &[A, R8Source], 1),
+        0o240..=0o247 => opcode(Op::AndAR8, "and", &[A, R8Source], 1),
+        0o250..=0o257 => opcode(Op::XorAR8, "xor", &[A, R8Source], 1),
+        0o260..=0o267 => opcode(Op::OrAR8, "or", &[A, R8Source], 1),
+        0o270..=0o277 => opcode(Op::CpAR8, "cp", &[A, R8Source], 1),
+        0o306 => opcode(Op::AddAN8, "add", &[A, N8], 2),
+        0o316 => opcode(Op::AdcAN8, "adc", &[A, N8], 2),
+        0o326 => opcode(Op::SubAN8, "sub", &[A, N8], 2),
+        0o336 => opcode(Op::SbcAN8, "sbc", &[A, N8], 2),
+        0o346 => opcode(Op::AndAN8, "and", &[A, N8], 2),
+        0o356 => opcode(Op::XorAN8, "xor", &[A, N8], 2),
+        0o366 => opcode(Op::OrAN8, "or", &[A, N8], 2),
+        0o376 => opcode(Op::CpAN8, "cp", &[A, N8], 2),
+        0o300 | 0o310 | 0o320 | 0o330 => opcode(Op::RetCond, "ret", &[Cond], 1),
+        0o311 => opcode(Op::Ret, "ret", &[], 1),
+        0o331 => opcode(Op::Reti, "reti", &[], 1),
+        0o302 | 0o312 | 0o322 | 0o332 => opcode(Op::JpCondN16, "jp", &[Cond, A16], 3),
+        0o303 => opcode(Op::JpN16, "jp", &[A16], 3),
+        0o351 => opcode(Op::JpHl, "jp", &[HL], 1),
+        0o304 | 0o314 | 0o324 | 0o334 => opcode(Op::CallCondN16, "call", &[Cond, A16], 3),
+        0o315 => opcode(Op::CallN16, "call", &[A16], 3),
+        0o307 | 0o317 | 0o327 | 0o337 | 0o347 | 0o357 | 0o367 | 0o377 => {
+            opcode(Op::Rst, "rst", &[Vector], 1)
+        }
+        0o301 | 0o321 | 0o341 | 0o361 => opcode(Op::Pop, "pop", &[R16Stk], 1),
+        0o305 | 0o325 | 0o345 | 0o365 => opcode(Op::Push, "push", &[R16Stk], 1),
+        0o313 => opcode(Op::Prefix, "prefix", &[], 2),
+        0o342 => opcode(Op::LdhIndCA, "ldh", &[IND_C, A], 1),
+        0o340 => opcode(Op::LdhIndN8A, "ldh", &[HighN8, A], 2),
+        0o352 => opcode(Op::LdIndN16A, "ld", &[IndA16, A], 3),
+        0o362 => opcode(Op::LdhAIndC, "ldh", &[A, IND_C], 1),
+        0o360 => opcode(Op::LdhAIndN8, "ldh", &[A, HighN8], 2),
+        0o372 => opcode(Op::LdAIndN16, "ld", &[A, IndA16], 3),
+        0o350 => opcode(Op::AddSpN8, "add", &[SP, E8], 2),
+        0o370 => opcode(Op::LdHlSpPlusN8, "ld", &[HL, SpE8], 2),
+        0o371 => opcode(Op::LdSpHl, "ld", &[SP, HL], 1),
+        0o363 => opcode(Op::Di, "di", &[], 1),
+        0o373 => opcode(Op::Ei, "ei", &[], 1),
+        0o323 | 0o333 | 0o335 | 0o343 | 0o344 | 0o353 | 0o354 | 0o355 | 0o364 | 0o374 | 0o375 => {
+            opcode(Op::Illegal, "db", &[Operand::Opcode], 1)
+        }
+    }
+}
+
+const fn decode_prefix(instruction: u8) -> Opcode {
+    match instruction {
+        0o00..=0o07 => opcode(Op::Rlc, "rlc", &[R8Source], 2),
+        0o10..=0o17 => opcode(Op::Rrc, "rrc", &[R8Source], 2),
+        0o20..=0o27 => opcode(Op::Rl, "rl", &[R8Source], 2),
+        0o30..=0o37 => opcode(Op::Rr, "rr", &[R8Source], 2),
+        0o40..=0o47 => opcode(Op::Sla, "sla", &[R8Source], 2),
+        0o50..=0o57 => opcode(Op::Sra, "sra", &[R8Source], 2),
+        0o60..=0o67 => opcode(Op::Swap, "swap", &[R8Source], 2),
+        0o70..=0o77 => opcode(Op::Srl, "srl", &[R8Source], 2),
+        0o100..=0o177 => opcode(Op::Bit, "bit", &[Bit, R8Source], 2),
+        0o200..=0o277 => opcode(Op::Res, "res", &[Bit, R8Source], 2),
+        0o300..=0o377 => opcode(Op::Set, "set", &[Bit, R8Source], 2),
+    }
+}
+
+const fn table(prefixed: bool) -> [Opcode; 256] {
+    let mut table = [opcode(Op::Nop, "", &[], 0); 256];
+    let mut instruction = 0;
+    while instruction < table.len() {
+        table[instruction] = match prefixed {
+            true => decode_prefix(instruction as u8),
+            false => decode(instruction as u8),
+        };
+        instruction += 1;
+    }
+    table
+}
+
+pub static OPCODES: [Opcode; 256] = table(false);
+/// Opcodes after the `0xCB` prefix.
+pub static PREFIX_OPCODES: [Opcode; 256] = table(true);
@@ -0,0 +1,266 @@
+//! Runs the community SM83 SingleStepTests (one JSON file of cases per opcode) against `Cpu`.
+//!
+//! The test data isn't checked in; point `SM83_TESTS_DIR` at a checkout of the `v1` directory,
+//! or put it under `roms/sm83/v1`. `single_step_tests.json` next to this file holds a few cases in
+//! the same format, with cycles from the documented instruction timings, so that the comparison
+//! itself always runs.
+
+use crate::gb::bus::{Bus, FlatRam};
+use crate::gb::cpu::Cpu;
+use anyhow::Result;
+use serde::Deserialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// One M-cycle: the address and data pins, and activity like `r-m` for a read or `-wm` for a
+/// write. Cycles without any pin activity are `null`.
+type Cycle = Option<(Option<u16>, Option<u8>, String)>;
+
+#[derive(Deserialize)]
+struct TestCase {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    cycles: Vec<Cycle>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+struct State {
+    a: u8,
+    b: u8,
+    c: u8,
+    d: u8,
+    e: u8,
+    f: u8,
+    h: u8,
+    l: u8,
+    pc: u16,
+    sp: u16,
+    ime: u8,
+    /// The IE register at FFFF, when the case gives it.
+    #[serde(default)]
+    ie: Option<u8>,
+    ram: Vec<(u16, u8)>,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Access {
+    Read(u16, u8),
+    Write(u16, u8),
+}
+
+struct TestBus {
+    ram: FlatRam,
+    /// Each access with the M-cycle it happened in, counting from the opcode fetch.
+    accesses: Vec<(usize, Access)>,
+    cycle: usize,
+}
+
+impl TestBus {
+    fn access(&mut self, access: Access) {
+        self.accesses.push((self.cycle, access));
+        self.cycle += 1;
+    }
+}
+
+impl Bus for TestBus {
+    fn read(&mut self, addr: u16) -> Result<u8> {
+        let val = self.ram.read(addr)?;
+        self.access(Access::Read(addr, val));
+        Ok(val)
+    }
+
+    fn write(&mut self, addr: u16, val: u8) -> Result<()> {
+        self.ram.write(addr, val)?;
+        self.access(Access::Write(addr, val));
+        Ok(())
+    }
+
+    fn tick(&mut self, cycles: u8) -> Result<()> {
+        self.ram.tick(cycles)
+    }
+
+    fn idle(&mut self) {
+        self.cycle += 1;
+    }
+}
+
+impl Cpu {
+    fn load_state(&mut self, state: &State) {
+        self.a = state.a;
+        self.b = state.b;
+        self.c = state.c;
+        self.d = state.d;
+        self.e = state.e;
+        self.f = state.f;
+        self.h = state.h;
+        self.l = state.l;
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.ime = state.ime != 0;
+    }
+
+    fn save_state(
+        &self,
+        bus: &mut TestBus,
+        ie: bool,
+        addresses: impl Iterator<Item = u16>,
+    ) -> Result<State> {
+        Ok(State {
+            a: self.a,
+            b: self.b,
+            c: self.c,
+            d: self.d,
+            e: self.e,
+            f: self.f,
+            h: self.h,
+            l: self.l,
+            pc: self.pc,
+            sp: self.sp,
+            ime: u8::from(self.ime),
+            ie: match ie {
+                true => Some(bus.ram.read(0xFFFF)?),
+                false => None,
+            },
+            ram: addresses
+                .map(|addr| Ok((addr, bus.ram.read(addr)?)))
+                .collect::<Result<Vec<(u16, u8)>>>()?,
+        })
+    }
+}
+
+fn run_case(case: &TestCase) -> Result<Vec<String>> {
+    let mut bus = TestBus {
+        ram: FlatRam::new(),
+        accesses: vec![],
+        cycle: 0,
+    };
+    if let Some(ie) = case.initial.ie {
+        bus.ram.load(0xFFFF, &[ie])?;
+    }
+    for (addr, val) in &case.initial.ram {
+        bus.ram.load(*addr, &[*val])?;
+    }
+    let mut cpu = Cpu::new();
+    cpu.load_state(&case.initial);
+
+    cpu.execute_next_instruction(&mut bus)?;
+
+    let mut mismatches = vec![];
+    let actual = cpu.save_state(
+        &mut bus,
+        case.expected.ie.is_some(),
+        case.expected.ram.iter().map(|(addr, _)| *addr),
+    )?;
+    if actual != case.expected {
+        mismatches.push(format!(
+            "state: expected {:?}, got {:?}",
+            case.expected, actual
+        ));
+    }
+
+    let expected_accesses: Vec<(usize, Access)> = case
+        .cycles
+        .iter()
+        .enumerate()
+        .filter_map(|(cycle, pins)| match pins.as_ref()? {
+            (Some(addr), Some(val), activity) if activity.contains('r') => {
+                Some((cycle, Access::Read(*addr, *val)))
+            }
+            (Some(addr), Some(val), activity) if activity.contains('w') => {
+                Some((cycle, Access::Write(*addr, *val)))
+            }
+            _ => None,
+        })
+        .collect();
+    if bus.accesses != expected_accesses {
+        mismatches.push(format!(
+            "bus: expected {:?}, got {:?}",
+            expected_accesses, bus.accesses
+        ));
+    }
+
+    if bus.ram.cycles() != case.cycles.len() as u64 {
+        mismatches.push(format!(
+            "cycles: expected {}, got {}",
+            case.cycles.len(),
+            bus.ram.cycles()
+        ));
+    }
+
+    Ok(mismatches)
+}
+
+fn run_file(path: &Path) -> Result<Vec<String>> {
+    run_cases(&fs::read_to_string(path)?)
+}
+
+fn run_cases(json: &str) -> Result<Vec<String>> {
+    let cases: Vec<TestCase> = serde_json::from_str(json)?;
+    let mut failures = vec![];
+    for case in &cases {
+        match run_case(case) {
+            Ok(mismatches) => failures.extend(
+                mismatches
+                    .into_iter()
+                    .map(|mismatch| format!("{}: {}", case.name, mismatch)),
+            ),
+            Err(e) => failures.push(format!("{}: {}", case.name, e)),
+        }
+    }
+    Ok(failures)
+}
+
+fn tests_dir() -> PathBuf {
+    std::env::var_os("SM83_TESTS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("roms/sm83/v1"))
+}
+
+#[test]
+fn test_fixture() -> Result<()> {
+    let failures = run_cases(include_str!("single_step_tests.json"))?;
+    assert!(failures.is_empty(), "{:#?}", failures);
+    Ok(())
+}
+
+#[test]
+fn test_sm83_single_step() -> Result<()> {
+    let dir = tests_dir();
+    if !dir.is_dir() {
+        // Straight to stderr, which the test harness doesn't capture, so the skip shows.
+        writeln!(
+            std::io::stderr(),
+            "Skipping SM83 single step tests: {} not found",
+            dir.display()
+        )?;
+        return Ok(());
+    }
+
+    let mut paths = fs::read_dir(&dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    paths.sort();
+
+    let mut failed_files = vec![];
+    for path in paths
+        .iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+    {
+        let failures = run_file(path)?;
+        if let Some(first) = failures.first() {
+            println!(
+                "{}: {} failures, first: {}",
+                path.display(),
+                failures.len(),
+                first
+            );
+            failed_files.push(path.display().to_string());
+        }
+    }
+
+    assert!(failed_files.is_empty(), "Failed: {:?}", failed_files);
+    Ok(())
+}
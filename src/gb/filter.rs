@@ -0,0 +1,266 @@
+//! CPU post-processing of the palette-mapped screen, so the same output is available to the
+//! window, screenshots and headless runs.
+
+use crate::gb::screen::{FrameBuffer, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::gb::Palette;
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+type Rgb = [u8; 3];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    /// AdvMAME2x: doubles the resolution, rounding off diagonal staircases.
+    Scale2x,
+    /// AdvMAME3x: the same at three times the resolution.
+    Scale3x,
+    /// Every pixel becomes a 3x3 dot with a darker gap, like the DMG's LCD matrix.
+    LcdGrid,
+}
+
+pub const FILTERS: [(&str, Filter); 4] = [
+    ("nearest", Filter::Nearest),
+    ("scale2x", Filter::Scale2x),
+    ("scale3x", Filter::Scale3x),
+    ("lcd", Filter::LcdGrid),
+];
+
+impl Filter {
+    pub fn by_name(name: &str) -> Option<Filter> {
+        FILTERS
+            .iter()
+            .find(|(filter, _)| *filter == name)
+            .map(|(_, filter)| *filter)
+    }
+
+    pub fn name(self) -> &'static str {
+        FILTERS.iter().find(|(_, f)| *f == self).unwrap().0
+    }
+
+    /// How many times larger the output is in each direction.
+    pub fn factor(self) -> usize {
+        match self {
+            Filter::Nearest => 1,
+            Filter::Scale2x => 2,
+            Filter::Scale3x | Filter::LcdGrid => 3,
+        }
+    }
+}
+
+/// Packed RGB24 rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(frame_buffer: &FrameBuffer, palette: &Palette) -> Image {
+        Image {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            rgb: frame_buffer.to_rgb(palette, 1),
+        }
+    }
+
+    /// The screen through `filter`, doubled first if it needs room for `scanlines`.
+    pub fn filtered(
+        frame_buffer: &FrameBuffer,
+        palette: &Palette,
+        filter: Filter,
+        scanlines: bool,
+    ) -> Image {
+        Image::new(frame_buffer, palette).post_process(filter, scanlines)
+    }
+
+    /// Applies `filter` to a screen-sized image, doubling it first if it needs room for
+    /// `scanlines`.
+    pub fn post_process(&self, filter: Filter, scanlines: bool) -> Image {
+        let image = self.filter(filter);
+        if !scanlines {
+            return image;
+        }
+        let mut image = match filter.factor() {
+            1 => image.scale(2),
+            _ => image,
+        };
+        image.scanlines(image.height / self.height);
+        image
+    }
+
+    fn get(&self, x: usize, y: usize) -> Rgb {
+        let offset = (y * self.width + x) * 3;
+        [self.rgb[offset], self.rgb[offset + 1], self.rgb[offset + 2]]
+    }
+
+    /// Maps every pixel, with its neighbors clamped at the edges, to a `factor`x`factor` block.
+    fn map_blocks(&self, factor: usize, block: impl Fn(&Neighbors) -> Vec<Rgb>) -> Image {
+        let width = self.width * factor;
+        let mut rgb = vec![0; width * self.height * factor * 3];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let at = |dx: isize, dy: isize| {
+                    let x = x.saturating_add_signed(dx).min(self.width - 1);
+                    let y = y.saturating_add_signed(dy).min(self.height - 1);
+                    self.get(x, y)
+                };
+                let neighbors = [
+                    [at(-1, -1), at(0, -1), at(1, -1)],
+                    [at(-1, 0), at(0, 0), at(1, 0)],
+                    [at(-1, 1), at(0, 1), at(1, 1)],
+                ];
+                for (i, pixel) in block(&neighbors).iter().enumerate() {
+                    let (bx, by) = (x * factor + i % factor, y * factor + i / factor);
+                    let offset = (by * width + bx) * 3;
+                    rgb[offset..offset + 3].copy_from_slice(pixel);
+                }
+            }
+        }
+        Image {
+            width,
+            height: self.height * factor,
+            rgb,
+        }
+    }
+
+    /// Nearest neighbor upscaling.
+    pub fn scale(&self, factor: usize) -> Image {
+        self.map_blocks(factor, |n| vec![n[1][1]; factor * factor])
+    }
+
+    pub fn filter(&self, filter: Filter) -> Image {
+        match filter {
+            Filter::Nearest => self.clone(),
+            Filter::Scale2x => self.map_blocks(2, scale2x),
+            Filter::Scale3x => self.map_blocks(3, scale3x),
+            Filter::LcdGrid => self.map_blocks(3, |n| {
+                let dot = n[1][1];
+                let gap = dot.map(|c| (u16::from(c) * 3 / 4) as u8);
+                vec![dot, dot, gap, dot, dot, gap, gap, gap, gap]
+            }),
+        }
+    }
+
+    /// Halves the brightness of the last of every `rows` rows.
+    pub fn scanlines(&mut self, rows: usize) {
+        let stride = self.width * 3;
+        for row in self.rgb.chunks_mut(stride).skip(rows - 1).step_by(rows) {
+            row.iter_mut().for_each(|c| *c /= 2);
+        }
+    }
+
+    pub fn write_png(&self, out: impl Write) -> Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.rgb)?;
+        Ok(())
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        self.write_png(BufWriter::new(file))
+    }
+}
+
+/// The 3x3 pixels centered on the one being scaled, row by row.
+type Neighbors = [[Rgb; 3]; 3];
+
+fn scale2x(n: &Neighbors) -> Vec<Rgb> {
+    let [[_, b, _], [d, e, f], [_, h, _]] = *n;
+    if b == h || d == f {
+        return vec![e; 4];
+    }
+    vec![
+        if d == b { d } else { e },
+        if b == f { f } else { e },
+        if d == h { d } else { e },
+        if h == f { f } else { e },
+    ]
+}
+
+fn scale3x(n: &Neighbors) -> Vec<Rgb> {
+    let [[a, b, c], [d, e, f], [g, h, i]] = *n;
+    if b == h || d == f {
+        return vec![e; 9];
+    }
+    vec![
+        if d == b { d } else { e },
+        if (d == b && e != c) || (b == f && e != a) {
+            b
+        } else {
+            e
+        },
+        if b == f { f } else { e },
+        if (d == b && e != g) || (d == h && e != a) {
+            d
+        } else {
+            e
+        },
+        e,
+        if (b == f && e != i) || (h == f && e != c) {
+            f
+        } else {
+            e
+        },
+        if d == h { d } else { e },
+        if (d == h && e != i) || (h == f && e != g) {
+            h
+        } else {
+            e
+        },
+        if h == f { f } else { e },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filter, Image};
+
+    /// A 2x2 image, black except for the top right corner.
+    fn corner() -> Image {
+        Image {
+            width: 2,
+            height: 2,
+            rgb: [0, 255, 0, 0]
+                .iter()
+                .flat_map(|c| [*c; 3])
+                .collect::<Vec<u8>>(),
+        }
+    }
+
+    fn gray(image: &Image) -> Vec<u8> {
+        image.rgb.iter().step_by(3).copied().collect()
+    }
+
+    #[test]
+    fn test_scale2x_rounds_corners() {
+        let image = corner().filter(Filter::Scale2x);
+        assert_eq!((image.width, image.height), (4, 4));
+        #[rustfmt::skip]
+        assert_eq!(gray(&image), [
+            0, 0, 255, 255,
+            0, 0, 0, 255,
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+        ]);
+    }
+
+    #[test]
+    fn test_scale_and_scanlines() {
+        let mut image = corner().scale(2);
+        image.scanlines(2);
+        #[rustfmt::skip]
+        assert_eq!(gray(&image), [
+            0, 0, 255, 255,
+            0, 0, 127, 127,
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+        ]);
+        assert_eq!(corner().filter(Filter::LcdGrid).width, 6);
+    }
+}
@@ -11,7 +11,8 @@ use crate::gb::memory::map::{
 };
 use crate::gb::memory::Memory;
 use crate::gb::Color::{Black, DarkGray, LightGray, White};
-use crate::gb::{Color, Pixel};
+use crate::gb::Layer::{Background, Object0, Object1};
+use crate::gb::{Color, Layer, Pixel};
 use anyhow::{anyhow, Result};
 use itertools::Itertools;
 use log::info;
@@ -240,7 +241,7 @@ impl GpuState {
     ) -> Result<(GpuState, Option<Pixel>)> {
         let dots = dots + 1;
         if pixel < PIXELS_PER_LINE {
-            let (color, is_window_pixel) =
+            let (color, layer, is_window_pixel) =
                 self.get_pixel_color(memory, lcd_info, scanline, window_line, pixel, &object_data)?;
 
             Ok((
@@ -256,6 +257,7 @@ impl GpuState {
                     x: pixel as u8,
                     y: scanline as u8,
                     color,
+                    layer,
                 }),
             ))
         } else {
@@ -359,7 +361,7 @@ impl GpuState {
         window_line: i32,
         pixel: i32,
         object_data: &[ObjData],
-    ) -> Result<(Color, bool)> {
+    ) -> Result<(Color, Layer, bool)> {
         let mb_obj_and_color_id = invert(
             object_data
                 .iter()
@@ -400,11 +402,11 @@ impl GpuState {
                 ),
             };
 
-        let color = match (mb_obj_and_color_id, mb_bg_color_id) {
-            (None, None) => White,
-            (None, Some(bg_color_id)) => self.get_bg_color(memory, bg_color_id)?,
+        let (color, layer) = match (mb_obj_and_color_id, mb_bg_color_id) {
+            (None, None) => (White, Background),
+            (None, Some(bg_color_id)) => (self.get_bg_color(memory, bg_color_id)?, Background),
             (Some((_, COLOR_ID_TRANSPARENT)), Some(bg_color_id)) => {
-                self.get_bg_color(memory, bg_color_id)?
+                (self.get_bg_color(memory, bg_color_id)?, Background)
             }
             (
                 Some((
@@ -418,7 +420,7 @@ impl GpuState {
                 Some(bg_color_id),
             ) => {
                 if *priority && bg_color_id != 0 {
-                    self.get_bg_color(memory, bg_color_id)?
+                    (self.get_bg_color(memory, bg_color_id)?, Background)
                 } else {
                     self.get_obj_color(memory, obj_color_id, *use_palette_1)?
                 }
@@ -428,7 +430,7 @@ impl GpuState {
             }
         };
 
-        Ok((color, is_window))
+        Ok((color, layer, is_window))
     }
 
     fn get_object_color_id(
@@ -539,9 +541,12 @@ impl GpuState {
         memory: &mut Memory,
         color_id: u8,
         obp1_palette: bool,
-    ) -> Result<Color> {
-        let palette = memory.read(if obp1_palette { OBP1 } else { OBP0 })?;
-        get_color(palette, color_id)
+    ) -> Result<(Color, Layer)> {
+        let (palette, layer) = match obp1_palette {
+            true => (OBP1, Object1),
+            false => (OBP0, Object0),
+        };
+        Ok((get_color(memory.read(palette)?, color_id)?, layer))
     }
 }
 
@@ -1,25 +1,34 @@
+use crate::gb::bus::{Bus, Fetch};
 use crate::gb::memory::cartridge::Cartridge;
+use crate::gb::memory::cheats::{Cheat, CheatCode};
+use crate::gb::memory::code_data_log::{CodeDataLog, CDL_DATA, CDL_OPCODE, CDL_OPERAND};
 use crate::gb::memory::external_ram::ExternalRam;
 use crate::gb::memory::high_ram::HighRam;
 use crate::gb::memory::interrupt_enable_register::InterruptEnableRegister;
 use crate::gb::memory::io_registers::IORegisters;
-use crate::gb::memory::map::{DMA, OBJ_ATTRIBUTES_BASE};
+use crate::gb::memory::joypad::Joypad;
+use crate::gb::memory::map::{BOOT, DMA, IF, JOYP, LY, OBJ_ATTRIBUTES_BASE};
 use crate::gb::memory::not_usable::NotUsable;
 use crate::gb::memory::object_attribute_memory::ObjectAttributeMemory;
 use crate::gb::memory::ram::Ram;
 use crate::gb::memory::video_ram::VideoRam;
+use crate::gb::memory::watchpoint::{WatchKind, Watchpoints};
 use std::path::Path;
 
 mod cartridge;
+pub mod cheats;
+pub mod code_data_log;
 mod external_ram;
 mod high_ram;
 mod interrupt_enable_register;
 mod io_registers;
+pub mod joypad;
 pub mod map;
 mod not_usable;
 mod object_attribute_memory;
 mod ram;
 mod video_ram;
+pub mod watchpoint;
 
 pub trait MemoryMappedDevice {
     fn read(&self, addr: u16) -> anyhow::Result<u8>;
@@ -27,6 +36,8 @@ pub trait MemoryMappedDevice {
 }
 
 pub struct Memory {
+    /// Mapped over the first 256 bytes of the cartridge until the boot ROM writes to BOOT.
+    pub boot_rom: Option<Vec<u8>>,
     cartridge: Cartridge,
     video_ram: VideoRam,
     external_ram: ExternalRam,
@@ -34,13 +45,19 @@ pub struct Memory {
     object_attribute_memory: ObjectAttributeMemory,
     not_usable: NotUsable,
     io_registers: IORegisters,
+    joypad: Joypad,
     high_ram: HighRam,
     interrupt_enable_register: InterruptEnableRegister,
+    pub ly_stub: bool,
+    pub watchpoints: Watchpoints,
+    pub code_data_log: Option<CodeDataLog>,
+    cheats: Vec<Cheat>,
 }
 
 impl Memory {
     pub fn new(cartridge: &Path) -> anyhow::Result<Memory> {
         Ok(Memory {
+            boot_rom: None,
             cartridge: Cartridge::new(cartridge)?,
             video_ram: VideoRam::new(),
             external_ram: ExternalRam::new(),
@@ -48,12 +65,146 @@ impl Memory {
             object_attribute_memory: ObjectAttributeMemory::new(),
             not_usable: NotUsable {},
             io_registers: IORegisters::new(),
+            joypad: Joypad::new(),
             high_ram: HighRam::new(),
             interrupt_enable_register: InterruptEnableRegister::new(),
+            ly_stub: false,
+            watchpoints: Watchpoints::new(),
+            code_data_log: None,
+            cheats: vec![],
         })
     }
 
+    pub fn rom_bank(&self) -> u16 {
+        self.cartridge.rom_bank()
+    }
+
+    /// The cartridge RAM bank mapped at 0xA000. No MBC is emulated yet, so this is always bank 0.
+    pub fn external_ram_bank(&self) -> u8 {
+        0
+    }
+
+    /// The work RAM bank mapped at 0xD000, which is always 1 on the DMG.
+    pub fn work_ram_bank(&self) -> u8 {
+        1
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn add_cheat(&mut self, cheat: Cheat) {
+        self.cheats.push(cheat);
+        self.update_patches();
+    }
+
+    pub fn remove_cheat(&mut self, index: usize) -> Option<Cheat> {
+        let cheat = (index < self.cheats.len()).then(|| self.cheats.remove(index));
+        self.update_patches();
+        cheat
+    }
+
+    /// Returns whether there is a cheat `index`.
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        let Some(cheat) = self.cheats.get_mut(index) else {
+            return false;
+        };
+        cheat.enabled = enabled;
+        self.update_patches();
+        true
+    }
+
+    fn update_patches(&mut self) {
+        let codes = self
+            .cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .flat_map(|cheat| cheat.codes().iter().copied());
+        self.cartridge.set_patches(codes);
+    }
+
+    /// Writes every enabled GameShark code whose bank is mapped, as the device does once a frame.
+    pub fn apply_gameshark_codes(&mut self) -> anyhow::Result<()> {
+        let codes: Vec<(u8, u16, u8)> = self
+            .cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .flat_map(|cheat| cheat.codes())
+            .filter_map(|code| match *code {
+                CheatCode::GameShark {
+                    bank,
+                    address,
+                    value,
+                } => Some((bank, address, value)),
+                CheatCode::GameGenie { .. } => None,
+            })
+            .collect();
+        for (bank, address, value) in codes {
+            let mapped = match (bank, address) {
+                (0x01, _) => true,
+                (0x80..=0x87, 0xD000..=0xDFFF) => (bank & 0x07).max(1) == self.work_ram_bank(),
+                (_, 0xA000..=0xBFFF) => bank == self.external_ram_bank(),
+                _ => true,
+            };
+            if mapped {
+                self.write(address, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.cartridge.has_battery()
+    }
+
+    pub fn external_ram(&self) -> &[u8] {
+        self.external_ram.data()
+    }
+
+    pub fn load_external_ram(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        self.external_ram.load(data)
+    }
+
+    pub fn rom_len(&self) -> usize {
+        self.cartridge.len()
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        self.cartridge.data()
+    }
+
+    pub fn buttons(&self) -> u8 {
+        self.joypad.pressed()
+    }
+
+    /// Presses exactly the buttons in `buttons`, requesting the joypad interrupt if a selected
+    /// line goes low.
+    pub fn set_buttons(&mut self, buttons: u8) -> anyhow::Result<()> {
+        if self.joypad.set_pressed(buttons) {
+            let if_reg = self.read(IF)?;
+            self.write(IF, if_reg | 1 << 4)?;
+        }
+        Ok(())
+    }
+
+    fn log_rom_access(&mut self, addr: u16, flag: u8) {
+        if let Some(code_data_log) = self.code_data_log.as_mut() {
+            let boot_rom_mapped = self.boot_rom.is_some() && addr < 0x0100;
+            if addr < 0x8000 && !boot_rom_mapped {
+                code_data_log.mark(self.cartridge.rom_offset(addr), flag);
+            }
+        }
+    }
+
     pub fn read(&mut self, addr: u16) -> anyhow::Result<u8> {
+        if self.ly_stub && addr == LY {
+            return Ok(0x90);
+        }
+        if let Some(boot_rom) = &self.boot_rom {
+            if let Some(val) = boot_rom.get(usize::from(addr)) {
+                return Ok(*val);
+            }
+        }
         let (device, offset) = self.get_device_and_offset(addr)?;
         device.read(offset)
     }
@@ -61,12 +212,20 @@ impl Memory {
     pub fn write(&mut self, addr: u16, val: u8) -> anyhow::Result<()> {
         match addr {
             DMA => {
-                for offset in 0..0xFF {
-                    let byte = self.read((u16::from(val) << 2) + offset)?;
+                let source = u16::from(val) << 8;
+                for offset in 0..0xA0 {
+                    let byte = self.read(source + offset)?;
+                    self.log_rom_access(source + offset, CDL_DATA);
                     self.write(OBJ_ATTRIBUTES_BASE + offset, byte)?;
                 }
                 Ok(())
             }
+            BOOT => {
+                if val != 0 {
+                    self.boot_rom = None;
+                }
+                self.io_registers.write(addr - 0xFF00, val)
+            }
             _ => {
                 let (device, offset) = self.get_device_and_offset(addr)?;
                 device.write(offset, val)
@@ -74,12 +233,6 @@ impl Memory {
         }
     }
 
-    pub fn write_16(&mut self, addr: u16, val: u16) -> anyhow::Result<()> {
-        let bytes = val.to_le_bytes();
-        self.write(addr, bytes[0])?;
-        self.write(addr + 1, bytes[1])
-    }
-
     fn get_device_and_offset(
         &mut self,
         addr: u16,
@@ -92,9 +245,55 @@ impl Memory {
             0xE000..=0xFDFF => Ok((self.ram.mirror_ram(), addr - 0xE000)),
             0xFE00..=0xFE9F => Ok((&mut self.object_attribute_memory, addr - 0xFE00)),
             0xFEA0..=0xFEFF => Ok((&mut self.not_usable, addr - 0xFEA0)),
-            0xFF00..=0xFF7F => Ok((&mut self.io_registers, addr - 0xFF00)),
+            JOYP => Ok((&mut self.joypad, 0)),
+            0xFF01..=0xFF7F => Ok((&mut self.io_registers, addr - 0xFF00)),
             0xFF80..=0xFFFE => Ok((&mut self.high_ram, addr - 0xFF80)),
             0xFFFF..=0xFFFF => Ok((&mut self.interrupt_enable_register, addr - 0xFFFF)),
         }
     }
 }
+
+/// The CPU's view of memory. Only these accesses are checked against watchpoints, so that PPU,
+/// timer and DMA traffic doesn't trigger them.
+impl Bus for Memory {
+    fn read(&mut self, addr: u16) -> anyhow::Result<u8> {
+        let val = Memory::read(self, addr)?;
+        if !self.watchpoints.is_empty() {
+            self.watchpoints.check(addr, WatchKind::Read, val, val);
+        }
+        self.log_rom_access(addr, CDL_DATA);
+        Ok(val)
+    }
+
+    fn fetch(&mut self, addr: u16, fetch: Fetch) -> anyhow::Result<u8> {
+        let val = Memory::read(self, addr)?;
+        if !self.watchpoints.is_empty() {
+            self.watchpoints.check(addr, WatchKind::Read, val, val);
+        }
+        self.log_rom_access(
+            addr,
+            match fetch {
+                Fetch::Opcode => CDL_OPCODE,
+                Fetch::Operand => CDL_OPERAND,
+            },
+        );
+        Ok(val)
+    }
+
+    fn write(&mut self, addr: u16, val: u8) -> anyhow::Result<()> {
+        if !self.watchpoints.is_empty() {
+            let old = Memory::read(self, addr)?;
+            self.watchpoints.check(addr, WatchKind::Write, old, val);
+        }
+        Memory::write(self, addr, val)
+    }
+
+    fn peek(&mut self, addr: u16) -> anyhow::Result<u8> {
+        Memory::read(self, addr)
+    }
+
+    fn tick(&mut self, _cycles: u8) -> anyhow::Result<()> {
+        // Peripherals are driven by `Clock` once the instruction has executed.
+        Ok(())
+    }
+}
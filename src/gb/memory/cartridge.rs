@@ -1,28 +1,101 @@
+use crate::gb::memory::cheats::CheatCode;
 use crate::gb::memory::map::MBC_TYPE;
 use crate::gb::memory::MemoryMappedDevice;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use log::warn;
 use memmap::{Mmap, MmapOptions};
 use std::fs::File;
+use std::io::ErrorKind;
 use std::path::Path;
 
 pub struct Cartridge {
     mmap: Mmap,
+    /// Enabled Game Genie codes.
+    patches: Vec<CheatCode>,
 }
 
 impl Cartridge {
     pub fn new(cartridge: &Path) -> Result<Cartridge> {
-        let file = File::open(cartridge)?;
+        let file = File::open(cartridge).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => anyhow!("ROM {} not found", cartridge.display()),
+            _ => anyhow!("Cannot read ROM {}: {}", cartridge.display(), e),
+        })?;
+        let metadata = file.metadata()?;
+        if metadata.is_dir() {
+            return Err(anyhow!("ROM {} is a directory", cartridge.display()));
+        }
+        // The smallest cartridges fill the 32 KiB ROM area, which reads aren't checked against.
+        if metadata.len() < 0x8000 {
+            return Err(anyhow!(
+                "{} is too small to be a Game Boy ROM ({} bytes)",
+                cartridge.display(),
+                metadata.len()
+            ));
+        }
         let mmap = unsafe { MmapOptions::new().map(&file)? };
         let mbc = mmap[usize::from(MBC_TYPE)];
         warn!("MBC: {}", mbc);
-        Ok(Cartridge { mmap })
+        Ok(Cartridge {
+            mmap,
+            patches: vec![],
+        })
+    }
+
+    /// Whether the cartridge keeps external RAM powered, so it should be saved between sessions.
+    pub fn has_battery(&self) -> bool {
+        matches!(
+            self.mmap[usize::from(MBC_TYPE)],
+            0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFF
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.mmap.len()
+    }
+
+    /// The whole ROM file.
+    pub fn data(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Offset in the ROM file of the byte the CPU sees at `addr`.
+    pub fn rom_offset(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => usize::from(addr),
+            _ => usize::from(self.rom_bank()) * 0x4000 + usize::from(addr - 0x4000),
+        }
+    }
+
+    /// Replaces the Game Genie codes applied to reads; other codes are ignored.
+    pub fn set_patches(&mut self, codes: impl IntoIterator<Item = CheatCode>) {
+        self.patches = codes
+            .into_iter()
+            .filter(|code| matches!(code, CheatCode::GameGenie { .. }))
+            .collect();
+    }
+
+    /// The bank mapped at 0x4000. No MBC is emulated yet, so this is always bank 1.
+    pub fn rom_bank(&self) -> u16 {
+        1
     }
 }
 
 impl MemoryMappedDevice for Cartridge {
     fn read(&self, addr: u16) -> Result<u8> {
-        Ok(self.mmap[usize::from(addr)])
+        let val = self.mmap[usize::from(addr)];
+        for patch in &self.patches {
+            if let CheatCode::GameGenie {
+                address,
+                value,
+                compare,
+            } = *patch
+            {
+                if address == addr && compare.is_none_or(|compare| compare == val) {
+                    return Ok(value);
+                }
+            }
+        }
+        Ok(val)
     }
 
     fn write(&mut self, _addr: u16, _val: u8) -> Result<()> {
@@ -0,0 +1,167 @@
+//! Game Genie codes, which patch what the CPU reads from ROM, and GameShark codes, which poke
+//! RAM once a frame.
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatCode {
+    /// `ABC-DEF` or `ABC-DEF-GHI`: reads of ROM `address` return `value` instead, only where the
+    /// ROM holds `compare` if given, so that the right bank is patched.
+    GameGenie {
+        address: u16,
+        value: u8,
+        compare: Option<u8>,
+    },
+    /// `TTVVAAAA`: writes `value` to `address` every frame. `bank` 01 writes to whatever is
+    /// mapped; otherwise it is the cartridge RAM bank for A000-BFFF, or 80-87 for a work RAM
+    /// bank at D000-DFFF.
+    GameShark { bank: u8, address: u16, value: u8 },
+}
+
+impl CheatCode {
+    pub fn parse(code: &str) -> Result<CheatCode> {
+        let digits = code
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_digit(16).map(|digit| digit as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| anyhow!("Invalid cheat code {}", code))?;
+        match *digits.as_slice() {
+            [a, b, c, d, e, f, ref compare @ ..] if compare.is_empty() || compare.len() == 3 => {
+                let address =
+                    u16::from(f ^ 0xF) << 12 | u16::from(c) << 8 | u16::from(d) << 4 | u16::from(e);
+                if address >= 0x8000 {
+                    return Err(anyhow!(
+                        "Game Genie code {} patches {:04X}, outside ROM",
+                        code,
+                        address
+                    ));
+                }
+                Ok(CheatCode::GameGenie {
+                    address,
+                    value: a << 4 | b,
+                    // The middle digit is only a checksum.
+                    compare: match *compare {
+                        [g, _, i] => Some((g << 4 | i).rotate_right(2) ^ 0xBA),
+                        _ => None,
+                    },
+                })
+            }
+            [t1, t0, v1, v0, a1, a0, a3, a2] if !code.contains('-') => Ok(CheatCode::GameShark {
+                bank: t1 << 4 | t0,
+                address: u16::from(a3) << 12
+                    | u16::from(a2) << 8
+                    | u16::from(a1) << 4
+                    | u16::from(a0),
+                value: v1 << 4 | v0,
+            }),
+            _ => Err(anyhow!(
+                "Invalid cheat code {}, expected ABC-DEF[-GHI] or TTVVAAAA",
+                code
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    /// As entered, with several codes joined by `+`.
+    pub code: String,
+    pub description: String,
+    pub enabled: bool,
+    codes: Vec<CheatCode>,
+}
+
+impl Cheat {
+    pub fn new(code: &str, description: &str) -> Result<Cheat> {
+        Ok(Cheat {
+            code: code.to_string(),
+            description: description.to_string(),
+            enabled: true,
+            codes: code
+                .split('+')
+                .map(|code| CheatCode::parse(code.trim()))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn codes(&self) -> &[CheatCode] {
+        &self.codes
+    }
+
+    pub fn load(path: &Path) -> Result<Vec<Cheat>> {
+        let text = fs::read_to_string(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Cheat::parse(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// One cheat per line: its codes joined by `+`, then an optional description. A leading `!`
+    /// loads it disabled and `;` starts a comment.
+    pub fn parse(text: &str) -> Result<Vec<Cheat>> {
+        let mut cheats = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (enabled, line) = match line.strip_prefix('!') {
+                Some(line) => (false, line.trim_start()),
+                None => (true, line),
+            };
+            let (code, description) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let cheat = Cheat::new(code, description.trim())
+                .map_err(|e| anyhow!("line {}: {}", number + 1, e))?;
+            cheats.push(Cheat { enabled, ..cheat });
+        }
+        Ok(cheats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cheat, CheatCode};
+
+    #[test]
+    fn test_parse_cheats() -> anyhow::Result<()> {
+        assert_eq!(
+            CheatCode::parse("991-01F-EA3")?,
+            CheatCode::GameGenie {
+                address: 0x0101,
+                value: 0x99,
+                compare: Some(0x42),
+            }
+        );
+        assert_eq!(
+            CheatCode::parse("00A-17B")?,
+            CheatCode::GameGenie {
+                address: 0x4A17,
+                value: 0x00,
+                compare: None,
+            }
+        );
+        assert_eq!(
+            CheatCode::parse("010138CD")?,
+            CheatCode::GameShark {
+                bank: 0x01,
+                address: 0xCD38,
+                value: 0x01,
+            }
+        );
+        assert!(CheatCode::parse("00A-170").is_err());
+        assert!(CheatCode::parse("0101-38CD").is_err());
+        assert!(CheatCode::parse("XYZ-123").is_err());
+
+        let cheats = Cheat::parse(
+            "; Tetris\n010138CD+00A-17B  Infinite lives\n\n! 01FF00C0 ; nothing yet\n",
+        )?;
+        assert_eq!(cheats.len(), 2);
+        assert_eq!(cheats[0].codes().len(), 2);
+        assert_eq!(cheats[0].description, "Infinite lives");
+        assert!(cheats[0].enabled);
+        assert_eq!(cheats[1].code, "01FF00C0");
+        assert!(!cheats[1].enabled);
+        assert!(Cheat::parse("ok\n").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+//! A code/data log: one flag byte per ROM byte, at the byte's offset in the ROM file so that the
+//! bank is implied.
+//!
+//! Logs are saved in BizHawk's CDL format for its Gambatte core, so they load there and in tools
+//! that read it: `BIZHAWK-CDL-2`, the sub type `GB`, then a block of flags per memory domain,
+//! with strings length-prefixed and numbers as little-endian i32s. Only ROM is logged; the other
+//! domains BizHawk expects are written with no flags set.
+
+use anyhow::{anyhow, Result};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// First byte of an executed instruction.
+pub const CDL_OPCODE: u8 = 0x01;
+/// Operand byte of an executed instruction.
+pub const CDL_OPERAND: u8 = 0x02;
+/// Read as data, by the CPU or OAM DMA.
+pub const CDL_DATA: u8 = 0x04;
+
+const MAGIC: &str = "BIZHAWK-CDL-2";
+/// Padded with spaces to 15 characters.
+const SUB_TYPE: &str = "GB             ";
+const HIGH_RAM_LEN: usize = 0x80;
+const WORK_RAM_LEN: usize = 0x2000;
+
+pub struct CodeDataLog {
+    flags: Vec<u8>,
+    /// Cartridge RAM isn't logged, but BizHawk expects a block for it when there is some.
+    cart_ram_len: usize,
+}
+
+impl CodeDataLog {
+    pub fn new(rom_len: usize, cart_ram_len: usize) -> CodeDataLog {
+        CodeDataLog {
+            flags: vec![0; rom_len],
+            cart_ram_len,
+        }
+    }
+
+    pub fn mark(&mut self, offset: usize, flag: u8) {
+        if let Some(flags) = self.flags.get_mut(offset) {
+            *flags |= flag;
+        }
+    }
+
+    pub fn flags(&self) -> &[u8] {
+        &self.flags
+    }
+
+    /// ORs in the ROM flags from a log of an earlier session, so coverage accumulates across
+    /// runs.
+    pub fn merge(&mut self, path: &Path) -> Result<()> {
+        let data = fs::read(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        let flags = parse_rom_flags(&data).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        if flags.len() != self.flags.len() {
+            return Err(anyhow!(
+                "{}: logs a {} byte ROM, not {}",
+                path.display(),
+                flags.len(),
+                self.flags.len()
+            ));
+        }
+        for (flag, old) in self.flags.iter_mut().zip(flags) {
+            *flag |= old;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        let mut out = BufWriter::new(file);
+        self.write(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> Result<()> {
+        let mut blocks = vec![
+            ("ROM", self.flags.clone()),
+            ("HRAM", vec![0; HIGH_RAM_LEN]),
+            ("WRAM", vec![0; WORK_RAM_LEN]),
+        ];
+        if self.cart_ram_len > 0 {
+            blocks.push(("CartRAM", vec![0; self.cart_ram_len]));
+        }
+        write_string(out, MAGIC)?;
+        write_string(out, SUB_TYPE)?;
+        out.write_all(&(blocks.len() as i32).to_le_bytes())?;
+        for (name, flags) in blocks {
+            write_string(out, name)?;
+            out.write_all(&(flags.len() as i32).to_le_bytes())?;
+            out.write_all(&flags)?;
+        }
+        Ok(())
+    }
+}
+
+/// A .NET `BinaryWriter` string: the UTF-8 length as a 7 bit encoded integer, then the bytes.
+/// Only the single byte lengths of the names here are supported.
+fn write_string(out: &mut impl Write, text: &str) -> Result<()> {
+    out.write_all(&[text.len() as u8])?;
+    out.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+/// Reads a log, returning the flags of its ROM block.
+fn parse_rom_flags(data: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = Reader { data };
+    if reader.string()? != MAGIC {
+        return Err(anyhow!("not a BizHawk CDL file"));
+    }
+    let sub_type = reader.string()?;
+    if sub_type.trim_end() != SUB_TYPE.trim_end() {
+        return Err(anyhow!("CDL file is for {}, not GB", sub_type.trim_end()));
+    }
+    let mut rom = None;
+    for _ in 0..reader.i32()? {
+        let name = reader.string()?;
+        let len = usize::try_from(reader.i32()?).map_err(|_| anyhow!("bad block length"))?;
+        let flags = reader.bytes(len)?;
+        if name == "ROM" {
+            rom = Some(flags.to_vec());
+        }
+    }
+    rom.ok_or_else(|| anyhow!("CDL file has no ROM block"))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(anyhow!("CDL file is truncated"));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.bytes(1)?[0];
+        if len >= 0x80 {
+            return Err(anyhow!("CDL string too long"));
+        }
+        Ok(String::from_utf8_lossy(self.bytes(usize::from(len))?).into_owned())
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        let bytes = self.bytes(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CodeDataLog, CDL_DATA, CDL_OPCODE};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_bizhawk_round_trip() -> anyhow::Result<()> {
+        let mut log = CodeDataLog::new(0x8000, 0);
+        log.mark(0x0100, CDL_OPCODE);
+        log.mark(0x4000, CDL_DATA);
+        let mut data = vec![];
+        log.write(&mut data)?;
+
+        let mut header = vec![13];
+        header.extend(b"BIZHAWK-CDL-2");
+        header.push(15);
+        header.extend(b"GB             ");
+        header.extend([3, 0, 0, 0, 3]);
+        header.extend(b"ROM");
+        header.extend([0x00, 0x80, 0x00, 0x00]);
+        assert_eq!(&data[..header.len()], header.as_slice());
+        assert_eq!(data[header.len() + 0x0100], CDL_OPCODE);
+        let wram = header.len() + 0x8000 + 1 + 4 + 4 + 0x80;
+        assert_eq!(&data[wram..wram + 5], b"\x04WRAM");
+        assert_eq!(data.len(), wram + 5 + 4 + 0x2000);
+
+        let dir = TempDir::new("boyohboy")?;
+        let path = dir.path().join("rom.cdl");
+        log.save(&path)?;
+        let mut merged = CodeDataLog::new(0x8000, 0);
+        merged.mark(0x0101, CDL_OPCODE);
+        merged.merge(&path)?;
+        assert_eq!(merged.flags()[0x0100], CDL_OPCODE);
+        assert_eq!(merged.flags()[0x0101], CDL_OPCODE);
+        assert_eq!(merged.flags()[0x4000], CDL_DATA);
+
+        assert!(CodeDataLog::new(0x4000, 0).merge(&path).is_err());
+        std::fs::write(&path, &data[..header.len() + 10])?;
+        assert!(CodeDataLog::new(0x8000, 0).merge(&path).is_err());
+        std::fs::write(&path, vec![0; 0x8000])?;
+        assert!(CodeDataLog::new(0x8000, 0).merge(&path).is_err());
+        Ok(())
+    }
+}
@@ -10,6 +10,23 @@ impl ExternalRam {
     pub fn new() -> ExternalRam {
         ExternalRam { ram: [0u8; SIZE] }
     }
+
+    pub fn data(&self) -> &[u8] {
+        &self.ram
+    }
+
+    /// Restores a battery save. Shorter saves only fill the start of RAM.
+    pub fn load(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        if data.len() > SIZE {
+            return Err(anyhow::anyhow!(
+                "Save is {} bytes but external RAM is only {}",
+                data.len(),
+                SIZE
+            ));
+        }
+        self.ram[..data.len()].copy_from_slice(data);
+        Ok(())
+    }
 }
 
 impl MemoryMappedDevice for ExternalRam {
@@ -6,10 +6,8 @@ pub struct IORegisters {
 }
 
 impl IORegisters {
-    #[allow(clippy::eq_op)]
     pub fn new() -> IORegisters {
         let mut ram = [0u8; SIZE];
-        ram[0xFF00 - 0xFF00] = 0xCF;
         ram[0xFF01 - 0xFF00] = 0x00;
         ram[0xFF02 - 0xFF00] = 0x7E;
         ram[0xFF04 - 0xFF00] = 0xAB;
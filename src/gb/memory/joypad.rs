@@ -0,0 +1,126 @@
+use crate::gb::memory::MemoryMappedDevice;
+
+/// The eight buttons, in the order of their bits in a button mask: the D-pad in the low nibble,
+/// the action buttons in the high one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+pub const BUTTONS: [(&str, Button); 8] = [
+    ("right", Button::Right),
+    ("left", Button::Left),
+    ("up", Button::Up),
+    ("down", Button::Down),
+    ("a", Button::A),
+    ("b", Button::B),
+    ("select", Button::Select),
+    ("start", Button::Start),
+];
+
+impl Button {
+    pub fn by_name(name: &str) -> Option<Button> {
+        BUTTONS
+            .iter()
+            .find(|(button, _)| button.eq_ignore_ascii_case(name))
+            .map(|(_, button)| *button)
+    }
+
+    pub fn name(self) -> &'static str {
+        BUTTONS[self as usize].0
+    }
+
+    pub fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+const SELECT_DPAD: u8 = 1 << 4;
+const SELECT_ACTION: u8 = 1 << 5;
+
+/// JOYP: the program selects the D-pad and/or action buttons with bits 4 and 5 and reads the
+/// selected ones back in the low nibble, 0 meaning pressed.
+pub struct Joypad {
+    select: u8,
+    /// Pressed buttons, one bit each as in `Button::mask`.
+    pressed: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Joypad {
+        Joypad {
+            select: SELECT_DPAD | SELECT_ACTION,
+            pressed: 0,
+        }
+    }
+
+    pub fn pressed(&self) -> u8 {
+        self.pressed
+    }
+
+    /// Updates the pressed buttons, returning whether a selected line went low, which requests
+    /// the joypad interrupt.
+    pub fn set_pressed(&mut self, pressed: u8) -> bool {
+        let before = self.lines();
+        self.pressed = pressed;
+        before & !self.lines() != 0
+    }
+
+    /// The low nibble of JOYP.
+    fn lines(&self) -> u8 {
+        let mut low = 0;
+        if self.select & SELECT_DPAD == 0 {
+            low |= self.pressed & 0x0F;
+        }
+        if self.select & SELECT_ACTION == 0 {
+            low |= self.pressed >> 4;
+        }
+        !low & 0x0F
+    }
+}
+
+impl MemoryMappedDevice for Joypad {
+    fn read(&self, _addr: u16) -> anyhow::Result<u8> {
+        Ok(0xC0 | self.select | self.lines())
+    }
+
+    fn write(&mut self, _addr: u16, val: u8) -> anyhow::Result<()> {
+        self.select = val & (SELECT_DPAD | SELECT_ACTION);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Button, Joypad};
+    use crate::gb::memory::MemoryMappedDevice;
+
+    #[test]
+    fn test_joypad() -> anyhow::Result<()> {
+        let mut joypad = Joypad::new();
+        assert_eq!(joypad.read(0)?, 0xFF);
+        // Nothing is selected, so pressing doesn't change what reads back.
+        assert!(!joypad.set_pressed(Button::Down.mask() | Button::Start.mask()));
+        assert_eq!(joypad.read(0)?, 0xFF);
+
+        joypad.write(0, 0x20)?;
+        assert_eq!(joypad.read(0)?, 0xE7);
+        joypad.write(0, 0x10)?;
+        assert_eq!(joypad.read(0)?, 0xD7);
+        joypad.write(0, 0x00)?;
+        assert_eq!(joypad.read(0)?, 0xC7);
+
+        assert!(joypad.set_pressed(Button::Down.mask() | Button::A.mask()));
+        assert_eq!(joypad.read(0)?, 0xC6);
+        assert!(!joypad.set_pressed(0));
+        assert_eq!(Button::by_name("Select"), Some(Button::Select));
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 pub const MBC_TYPE: u16 = 0x0147;
 pub const OBJ_TILES_BASE: u16 = 0x8000;
 pub const OBJ_ATTRIBUTES_BASE: u16 = 0xFE00;
+pub const JOYP: u16 = 0xFF00;
 pub const SB: u16 = 0xFF01;
 pub const SC: u16 = 0xFF02;
 pub const DIV: u16 = 0xFF04;
@@ -20,4 +21,5 @@ pub const OBP0: u16 = 0xFF48;
 pub const OBP1: u16 = 0xFF49;
 pub const WY: u16 = 0xFF4A;
 pub const WX: u16 = 0xFF4B;
+pub const BOOT: u16 = 0xFF50;
 pub const IE: u16 = 0xFFFF;
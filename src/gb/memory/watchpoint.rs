@@ -0,0 +1,144 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+impl WatchKind {
+    fn matches(&self, access: WatchKind) -> bool {
+        *self == WatchKind::Access || *self == access
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchAction {
+    /// Report the hit through `GameBoy::take_watch_hits` so the frontend can pause.
+    Break,
+    Log,
+}
+
+/// Watches CPU accesses to the inclusive address range `start..=end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub kind: WatchKind,
+    /// Only trigger when the value read or written equals this.
+    pub value: Option<u8>,
+    pub action: WatchAction,
+}
+
+impl Watchpoint {
+    fn matches(&self, addr: u16, access: WatchKind, val: u8) -> bool {
+        (self.start..=self.end).contains(&addr)
+            && self.kind.matches(access)
+            && self.value.is_none_or(|value| value == val)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchHit {
+    pub watchpoint: Watchpoint,
+    pub address: u16,
+    /// `Read` or `Write`, never `Access`.
+    pub access: WatchKind,
+    /// For reads `old` and `new` are both the value read.
+    pub old: u8,
+    pub new: u8,
+    /// The instruction that made the access.
+    pub pc: u16,
+    /// M-cycles since power on when the instruction started.
+    pub cycles: usize,
+}
+
+pub struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    hits: Vec<WatchHit>,
+    pc: u16,
+    cycles: usize,
+}
+
+impl Watchpoints {
+    pub fn new() -> Watchpoints {
+        Watchpoints {
+            watchpoints: vec![],
+            hits: vec![],
+            pc: 0,
+            cycles: 0,
+        }
+    }
+
+    pub fn add(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn remove(&mut self, watchpoint: &Watchpoint) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|w| w != watchpoint);
+        self.watchpoints.len() != len
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    /// Sets the instruction that subsequent hits are attributed to.
+    pub fn set_instruction(&mut self, pc: u16, cycles: usize) {
+        self.pc = pc;
+        self.cycles = cycles;
+    }
+
+    pub fn take_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.hits)
+    }
+
+    pub fn check(&mut self, addr: u16, access: WatchKind, old: u8, new: u8) {
+        for watchpoint in &self.watchpoints {
+            if watchpoint.matches(addr, access, new) {
+                self.hits.push(WatchHit {
+                    watchpoint: *watchpoint,
+                    address: addr,
+                    access,
+                    old,
+                    new,
+                    pc: self.pc,
+                    cycles: self.cycles,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WatchAction, WatchKind, Watchpoint, Watchpoints};
+
+    #[test]
+    fn test_range_kind_and_value() {
+        let mut watchpoints = Watchpoints::new();
+        watchpoints.add(Watchpoint {
+            start: 0xC000,
+            end: 0xC0FF,
+            kind: WatchKind::Write,
+            value: Some(0x42),
+            action: WatchAction::Break,
+        });
+        watchpoints.set_instruction(0x0150, 100);
+
+        watchpoints.check(0xC010, WatchKind::Read, 0x42, 0x42);
+        watchpoints.check(0xC010, WatchKind::Write, 0x00, 0x41);
+        watchpoints.check(0xC100, WatchKind::Write, 0x00, 0x42);
+        assert!(watchpoints.take_hits().is_empty());
+
+        watchpoints.check(0xC010, WatchKind::Write, 0x07, 0x42);
+        let hits = watchpoints.take_hits();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].old, hits[0].new), (0x07, 0x42));
+        assert_eq!((hits[0].pc, hits[0].cycles), (0x0150, 100));
+    }
+}
@@ -0,0 +1,241 @@
+//! Input movies: the buttons held during every frame from power on, so that a run can be
+//! replayed exactly. Frames here are fixed slices of `CYCLES_PER_FRAME` cycles rather than LCD
+//! frames, so input keeps its timing while the LCD is off.
+//!
+//! The file is text: a few `key value` header lines, then one line per frame with the held
+//! buttons as `RLUDABsS` (`.` when released) and a hash of the screen at the end of the frame.
+
+use crate::gb::memory::joypad::BUTTONS;
+use anyhow::{anyhow, Result};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const MAGIC: &str = "boyohboy-movie 1";
+/// One letter per button, in `Button::mask` bit order.
+const BUTTON_LETTERS: &[u8; 8] = b"RLUDABsS";
+
+/// A movie being recorded or played back, and the frame in progress.
+pub(crate) struct MovieSession {
+    pub movie: Movie,
+    pub frame: usize,
+    pub mode: MovieMode,
+}
+
+pub(crate) enum MovieMode {
+    /// Buttons set during a frame are held from the start of the next one, as on playback.
+    Recording {
+        next_buttons: u8,
+    },
+    Playing {
+        verify: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieFrame {
+    /// `Button::mask` bits.
+    pub buttons: u8,
+    /// `FrameBuffer::hash` at the end of the frame.
+    pub screen_hash: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    /// The version of the emulator that recorded it.
+    pub version: String,
+    /// CRC-32 of the ROM file.
+    pub rom_crc32: u32,
+    /// CRC-32 of the boot ROM, if the run started with one.
+    pub boot_rom_crc32: Option<u32>,
+    /// CRC-32 of the cartridge RAM at power on, which a battery save may have filled.
+    pub save_ram_crc32: u32,
+    /// Codes of the cheats enabled at power on, which stay as they are for the whole movie.
+    pub cheats: Vec<String>,
+    pub frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    pub fn new(rom_crc32: u32, boot_rom_crc32: Option<u32>, save_ram_crc32: u32) -> Movie {
+        Movie {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            rom_crc32,
+            boot_rom_crc32,
+            save_ram_crc32,
+            cheats: vec![],
+            frames: vec![],
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Movie> {
+        let text = fs::read_to_string(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Movie::parse(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        let mut out = BufWriter::new(file);
+        self.write(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> Result<()> {
+        writeln!(out, "{}", MAGIC)?;
+        writeln!(out, "version {}", self.version)?;
+        writeln!(out, "rom {:08X}", self.rom_crc32)?;
+        match self.boot_rom_crc32 {
+            Some(crc32) => writeln!(out, "boot-rom {:08X}", crc32)?,
+            None => writeln!(out, "boot-rom none")?,
+        }
+        writeln!(out, "save-ram {:08X}", self.save_ram_crc32)?;
+        for code in &self.cheats {
+            writeln!(out, "cheat {}", code)?;
+        }
+        writeln!(out, "start power-on")?;
+        writeln!(out, "frames {}", self.frames.len())?;
+        for frame in &self.frames {
+            let buttons: String = BUTTON_LETTERS
+                .iter()
+                .enumerate()
+                .map(|(bit, letter)| match frame.buttons & 1 << bit {
+                    0 => '.',
+                    _ => char::from(*letter),
+                })
+                .collect();
+            writeln!(out, "{} {:016X}", buttons, frame.screen_hash)?;
+        }
+        Ok(())
+    }
+
+    pub fn parse(text: &str) -> Result<Movie> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some(MAGIC) {
+            return Err(anyhow!("not a movie file"));
+        }
+        let mut movie = Movie::new(0, None, 0);
+        let mut rom = None;
+        let mut save_ram = None;
+        for (number, line) in lines {
+            let error = |message: &str| anyhow!("line {}: {}", number + 1, message);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(' ')
+                .ok_or_else(|| error("expected a value"))?;
+            let value = value.trim();
+            match key {
+                "version" => movie.version = value.to_string(),
+                "rom" => rom = Some(parse_crc32(value).ok_or_else(|| error("bad ROM CRC"))?),
+                "boot-rom" if value == "none" => movie.boot_rom_crc32 = None,
+                "boot-rom" => {
+                    movie.boot_rom_crc32 =
+                        Some(parse_crc32(value).ok_or_else(|| error("bad boot ROM CRC"))?)
+                }
+                "save-ram" => {
+                    save_ram = Some(parse_crc32(value).ok_or_else(|| error("bad save RAM CRC"))?)
+                }
+                "cheat" => movie.cheats.push(value.to_string()),
+                "start" if value == "power-on" => {}
+                "start" => return Err(error("only movies starting at power on are supported")),
+                "frames" => {
+                    let count = value.parse().map_err(|_| error("bad frame count"))?;
+                    movie.frames.reserve(count);
+                }
+                _ if key.len() == BUTTON_LETTERS.len() => {
+                    movie
+                        .frames
+                        .push(parse_frame(key, value).ok_or_else(|| error("bad frame"))?);
+                }
+                _ => return Err(error(&format!("unknown key {}", key))),
+            }
+        }
+        movie.rom_crc32 = rom.ok_or_else(|| anyhow!("movie has no ROM CRC"))?;
+        movie.save_ram_crc32 = save_ram.ok_or_else(|| anyhow!("movie has no save RAM CRC"))?;
+        Ok(movie)
+    }
+}
+
+fn parse_crc32(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
+
+fn parse_frame(buttons: &str, screen_hash: &str) -> Option<MovieFrame> {
+    let mut mask = 0;
+    for (bit, (c, letter)) in buttons.bytes().zip(BUTTON_LETTERS).enumerate() {
+        match c {
+            b'.' => {}
+            _ if c == *letter => mask |= 1 << bit,
+            _ => return None,
+        }
+    }
+    Some(MovieFrame {
+        buttons: mask,
+        screen_hash: u64::from_str_radix(screen_hash, 16).ok()?,
+    })
+}
+
+/// The CRC-32 used by zip and the No-Intro ROM databases.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
+
+/// Describes the buttons in `mask` by name, for desync reports.
+pub fn describe_buttons(mask: u8) -> String {
+    let names: Vec<&str> = BUTTONS
+        .iter()
+        .filter(|(_, button)| mask & button.mask() != 0)
+        .map(|(name, _)| *name)
+        .collect();
+    match names.is_empty() {
+        true => "none".to_string(),
+        false => names.join("+"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, Movie, MovieFrame};
+
+    #[test]
+    fn test_movie_round_trip() -> anyhow::Result<()> {
+        let mut movie = Movie::new(0x1234_ABCD, None, 0);
+        movie.cheats = vec![
+            "00C6FFD1".to_string(),
+            "991-01F-EA3+111-01F-000".to_string(),
+        ];
+        movie.frames = vec![
+            MovieFrame {
+                buttons: 0,
+                screen_hash: 1,
+            },
+            MovieFrame {
+                buttons: 0b1001_0001,
+                screen_hash: u64::MAX,
+            },
+        ];
+        let mut text = vec![];
+        movie.write(&mut text)?;
+        let text = String::from_utf8(text)?;
+        assert!(text.contains("\nR...A..S FFFFFFFFFFFFFFFF\n"));
+        assert_eq!(Movie::parse(&text)?, movie);
+
+        assert!(Movie::parse("rom 1234ABCD\n").is_err());
+        assert!(Movie::parse(&text.replace("power-on", "savestate")).is_err());
+        assert!(Movie::parse(&text.replace("R...A..S", "X...A..S")).is_err());
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+use crate::gb::{Color, Layer};
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+
+/// RGB values for the four DMG shades, lightest first.
+pub type Shades = [[u8; 3]; 4];
+
+/// Shades for the background and window and for each object palette, the way a CGB colorizes
+/// DMG games.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub bg: Shades,
+    pub obj0: Shades,
+    pub obj1: Shades,
+}
+
+const GRAY: Shades = [[255, 255, 255], [170, 170, 170], [85, 85, 85], [0, 0, 0]];
+
+pub const PRESETS: [(&str, Palette); 6] = [
+    ("gray", Palette::uniform(GRAY)),
+    (
+        "dmg",
+        Palette::uniform([
+            [0x9B, 0xBC, 0x0F],
+            [0x8B, 0xAC, 0x0F],
+            [0x30, 0x62, 0x30],
+            [0x0F, 0x38, 0x0F],
+        ]),
+    ),
+    (
+        "pocket",
+        Palette::uniform([
+            [0xC4, 0xCF, 0xA1],
+            [0x8B, 0x95, 0x6D],
+            [0x4D, 0x53, 0x3C],
+            [0x1F, 0x1F, 0x1F],
+        ]),
+    ),
+    (
+        "light",
+        Palette::uniform([
+            [0x00, 0xB5, 0x81],
+            [0x00, 0x9A, 0x71],
+            [0x00, 0x69, 0x4A],
+            [0x00, 0x4F, 0x3B],
+        ]),
+    ),
+    // The middle shades in hues that stay apart for color blind players and on washed out screens.
+    (
+        "high-contrast",
+        Palette::uniform([
+            [0xFF, 0xFF, 0xFF],
+            [0xFF, 0xB0, 0x00],
+            [0x00, 0x5F, 0xFF],
+            [0x00, 0x00, 0x00],
+        ]),
+    ),
+    // What the CGB boot ROM picks for games it has no palette for.
+    (
+        "cgb",
+        Palette {
+            bg: [
+                [0xFF, 0xFF, 0xFF],
+                [0x7B, 0xFF, 0x31],
+                [0x00, 0x63, 0xC5],
+                [0x00, 0x00, 0x00],
+            ],
+            obj0: [
+                [0xFF, 0xFF, 0xFF],
+                [0xFF, 0x84, 0x84],
+                [0x94, 0x3A, 0x3A],
+                [0x00, 0x00, 0x00],
+            ],
+            obj1: [
+                [0xFF, 0xFF, 0xFF],
+                [0xFF, 0x84, 0x84],
+                [0x94, 0x3A, 0x3A],
+                [0x00, 0x00, 0x00],
+            ],
+        },
+    ),
+];
+
+impl Palette {
+    /// The same shades for every layer.
+    pub const fn uniform(shades: Shades) -> Palette {
+        Palette {
+            bg: shades,
+            obj0: shades,
+            obj1: shades,
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Palette> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, palette)| *palette)
+    }
+
+    pub fn load(path: &Path) -> Result<Vec<(String, Palette)>> {
+        let text = fs::read_to_string(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Palette::parse(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// Named palettes, one section each, with four `RRGGBB` shades per layer, lightest first:
+    ///
+    /// ```text
+    /// [sunset]
+    /// bg   = FFF6D3 F9A875 EB6B6F 7C3F58
+    /// obj0 = FFFFFF F9A875 EB6B6F 000000
+    /// ```
+    ///
+    /// `obj0` and `obj1` default to the `bg` shades. `;` starts a comment.
+    pub fn parse(text: &str) -> Result<Vec<(String, Palette)>> {
+        let mut sections: Vec<(String, [Option<Shades>; 3])> = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: &str| anyhow!("line {}: {}", number + 1, message);
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                sections.push((name.trim().to_string(), [None; 3]));
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected [name] or layer = shades"))?;
+            let layer = match key.trim() {
+                "bg" => 0,
+                "obj0" => 1,
+                "obj1" => 2,
+                key => return Err(error(&format!("unknown layer {}", key))),
+            };
+            let shades = parse_shades(value).ok_or_else(|| error("expected four RRGGBB shades"))?;
+            let (_, layers) = sections
+                .last_mut()
+                .ok_or_else(|| error("shades before the first [name]"))?;
+            layers[layer] = Some(shades);
+        }
+        sections
+            .into_iter()
+            .map(|(name, [bg, obj0, obj1])| {
+                let bg = bg.ok_or_else(|| anyhow!("palette {} has no bg shades", name))?;
+                let palette = Palette {
+                    bg,
+                    obj0: obj0.unwrap_or(bg),
+                    obj1: obj1.unwrap_or(bg),
+                };
+                Ok((name, palette))
+            })
+            .collect()
+    }
+
+    pub fn rgb(&self, color: Color, layer: Layer) -> [u8; 3] {
+        let shades = match layer {
+            Layer::Background => &self.bg,
+            Layer::Object0 => &self.obj0,
+            Layer::Object1 => &self.obj1,
+        };
+        shades[match color {
+            Color::White => 0,
+            Color::LightGray => 1,
+            Color::DarkGray => 2,
+            Color::Black => 3,
+        }]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        PRESETS[0].1
+    }
+}
+
+fn parse_shades(text: &str) -> Option<Shades> {
+    let shades: Vec<[u8; 3]> = text
+        .split_whitespace()
+        .map(|shade| {
+            let shade = shade.trim_start_matches('#');
+            let rgb = u32::from_str_radix(shade, 16)
+                .ok()
+                .filter(|_| shade.len() == 6)?;
+            Some([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+        })
+        .collect::<Option<_>>()?;
+    shades.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Palette;
+    use crate::gb::{Color, Layer};
+
+    #[test]
+    fn test_parse() -> anyhow::Result<()> {
+        let palettes = Palette::parse(
+            "; Mine\n\
+             [sunset]\n\
+             bg   = FFF6D3 F9A875 EB6B6F 7C3F58\n\
+             obj1 = #FFFFFF #F9A875 #EB6B6F #000000\n",
+        )?;
+        let (name, palette) = &palettes[0];
+        assert_eq!(name, "sunset");
+        assert_eq!(palette.obj0, palette.bg);
+        assert_eq!(
+            palette.rgb(Color::Black, Layer::Background),
+            [0x7C, 0x3F, 0x58]
+        );
+        assert_eq!(palette.rgb(Color::Black, Layer::Object1), [0, 0, 0]);
+
+        assert!(Palette::parse("bg = FFFFFF AAAAAA 555555 000000").is_err());
+        assert!(Palette::parse("[a]\nbg = FFFFFF AAAAAA 555555").is_err());
+        assert!(Palette::parse("[a]\nobj0 = FFFFFF AAAAAA 555555 000000").is_err());
+        assert!(Palette::parse("[a]\nwin = FFFFFF AAAAAA 555555 000000").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+//! Accumulates executed M-cycles per address, per ROM bank and per call stack.
+
+use crate::gb::call_stack::Frame;
+use crate::gb::symbols::Symbols;
+use anyhow::Result;
+use itertools::Itertools;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Addresses listed in the "hottest addresses" section of the report.
+const REPORT_ADDRESSES: usize = 50;
+
+/// A code location: the ROM bank it was executed from and its address.
+type Location = (u16, u16);
+
+pub struct Profiler {
+    by_location: HashMap<Location, u64>,
+    /// Keyed by the targets of the active frames, outermost first.
+    by_stack: HashMap<Vec<Location>, u64>,
+    /// Reused to look up `by_stack` without allocating on every instruction.
+    stack: Vec<Location>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            by_location: HashMap::new(),
+            by_stack: HashMap::new(),
+            stack: vec![],
+        }
+    }
+
+    pub fn record(&mut self, pc: u16, rom_bank: u16, frames: &[Frame], cycles: u8) {
+        let cycles = u64::from(cycles);
+        *self.by_location.entry(location(pc, rom_bank)).or_insert(0) += cycles;
+
+        self.stack.clear();
+        self.stack.extend(
+            frames
+                .iter()
+                .map(|frame| location(frame.target, frame.bank)),
+        );
+        match self.by_stack.get_mut(self.stack.as_slice()) {
+            Some(total) => *total += cycles,
+            None => {
+                self.by_stack.insert(self.stack.clone(), cycles);
+            }
+        }
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.by_location.values().sum()
+    }
+
+    pub fn cycles_at(&self, pc: u16, rom_bank: u16) -> u64 {
+        self.by_location
+            .get(&location(pc, rom_bank))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// One `outer;inner cycles` line per call stack, as consumed by flamegraph.pl and inferno.
+    pub fn write_folded(&self, out: &mut impl Write, symbols: &Symbols) -> Result<()> {
+        for (stack, cycles) in self.by_stack.iter().sorted() {
+            let names = std::iter::once("(top)".to_string())
+                .chain(stack.iter().map(|location| name(symbols, *location)))
+                .join(";");
+            writeln!(out, "{} {}", names, cycles)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_report(&self, out: &mut impl Write, symbols: &Symbols) -> Result<()> {
+        let total = self.total_cycles().max(1);
+        let percent = |cycles: u64| cycles as f64 * 100.0 / total as f64;
+
+        writeln!(out, "Total: {} M-cycles", self.total_cycles())?;
+
+        writeln!(out, "\nBy bank:")?;
+        let mut banks: HashMap<u16, u64> = HashMap::new();
+        for ((bank, _), cycles) in &self.by_location {
+            *banks.entry(*bank).or_insert(0) += cycles;
+        }
+        for (bank, cycles) in banks
+            .iter()
+            .sorted_by_key(|(bank, cycles)| (Reverse(**cycles), **bank))
+        {
+            writeln!(
+                out,
+                "{:>12} {:>6.2}%  bank {:02X}",
+                cycles,
+                percent(*cycles),
+                bank
+            )?;
+        }
+
+        writeln!(out, "\nBy function (self, inclusive):")?;
+        let mut functions: HashMap<Option<Location>, (u64, u64)> = HashMap::new();
+        for (stack, cycles) in &self.by_stack {
+            functions.entry(stack.last().copied()).or_default().0 += cycles;
+            // Recursion shouldn't count the same cycles twice towards a function's inclusive time.
+            for function in std::iter::once(None)
+                .chain(stack.iter().copied().map(Some))
+                .unique()
+            {
+                functions.entry(function).or_default().1 += cycles;
+            }
+        }
+        for (function, (self_cycles, inclusive)) in functions
+            .iter()
+            .sorted_by_key(|(function, (_, inclusive))| (Reverse(*inclusive), **function))
+        {
+            let name = match function {
+                Some(location) => name(symbols, *location),
+                None => "(top)".to_string(),
+            };
+            writeln!(
+                out,
+                "{:>12} {:>6.2}% {:>12} {:>6.2}%  {}",
+                self_cycles,
+                percent(*self_cycles),
+                inclusive,
+                percent(*inclusive),
+                name
+            )?;
+        }
+
+        writeln!(out, "\nHottest addresses:")?;
+        for ((bank, pc), cycles) in self
+            .by_location
+            .iter()
+            .sorted_by_key(|(location, cycles)| (Reverse(**cycles), **location))
+            .take(REPORT_ADDRESSES)
+        {
+            let label = symbols
+                .symbolize(*pc, *bank)
+                .map(|label| format!("  {}", label))
+                .unwrap_or_default();
+            writeln!(
+                out,
+                "{:>12} {:>6.2}%  {:02X}:{:04X}{}",
+                cycles,
+                percent(*cycles),
+                bank,
+                pc,
+                label
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler::new()
+    }
+}
+
+/// Only addresses in the switchable ROM region belong to a bank other than 0.
+fn location(address: u16, rom_bank: u16) -> Location {
+    match address {
+        0x4000..=0x7FFF => (rom_bank, address),
+        _ => (0, address),
+    }
+}
+
+fn name(symbols: &Symbols, (bank, address): Location) -> String {
+    symbols
+        .symbolize(address, bank)
+        .unwrap_or_else(|| format!("{:02X}:{:04X}", bank, address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Profiler;
+    use crate::gb::call_stack::{Frame, FrameKind};
+    use crate::gb::symbols::Symbols;
+
+    #[test]
+    fn test_folded_stacks() -> anyhow::Result<()> {
+        let symbols = Symbols::parse("00:0150 Main\n00:0200 Func\n02:4000 Banked\n")?;
+        let frame = Frame {
+            kind: FrameKind::Call,
+            caller: 0x0152,
+            target: 0x0200,
+            bank: 1,
+            return_address: 0x0155,
+            sp: 0xFFFC,
+        };
+        // Called in bank 2, which has since switched out.
+        let banked = Frame {
+            target: 0x4000,
+            bank: 2,
+            ..frame
+        };
+        let mut profiler = Profiler::new();
+        profiler.record(0x0152, 1, &[], 6);
+        profiler.record(0x0200, 1, &[frame], 1);
+        profiler.record(0x0201, 1, &[frame], 4);
+        profiler.record(0x0200, 1, &[frame], 1);
+        profiler.record(0x0202, 3, &[frame, banked], 2);
+
+        assert_eq!(profiler.total_cycles(), 14);
+        assert_eq!(profiler.cycles_at(0x0200, 1), 2);
+
+        let mut folded = vec![];
+        profiler.write_folded(&mut folded, &symbols)?;
+        assert_eq!(
+            String::from_utf8(folded)?,
+            "(top) 6\n(top);Func 6\n(top);Func;Banked 2\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+//! Records every completed frame as a YUV4MPEG2 (`.y4m`) stream, which ffmpeg, mpv and most
+//! editors read directly. Frames are 4:4:4 so the four shades survive without chroma bleeding.
+//! There is no audio yet, so recordings are video only.
+
+use crate::gb::screen::{FrameBuffer, CYCLES_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::gb::{Color, Layer, Palette};
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// T-cycles per second over T-cycles per frame, about 59.73 fps.
+const FRAME_RATE: (usize, usize) = (4_194_304, CYCLES_PER_FRAME * 4);
+
+pub struct Recorder {
+    out: Box<dyn Write>,
+    /// Limited range BT.601 Y, Cb and Cr for each layer and shade.
+    yuv: [[[u8; 3]; 4]; 3],
+    planes: Vec<u8>,
+    frames: u64,
+}
+
+impl Recorder {
+    pub fn new(mut out: impl Write + 'static, palette: &Palette) -> Result<Recorder> {
+        writeln!(
+            out,
+            "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444",
+            SCREEN_WIDTH, SCREEN_HEIGHT, FRAME_RATE.0, FRAME_RATE.1
+        )?;
+        Ok(Recorder {
+            out: Box::new(out),
+            yuv: [palette.bg, palette.obj0, palette.obj1].map(|shades| shades.map(yuv)),
+            planes: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 3],
+            frames: 0,
+        })
+    }
+
+    pub fn create(path: &Path, palette: &Palette) -> Result<Recorder> {
+        let file = File::create(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Recorder::new(BufWriter::new(file), palette)
+    }
+
+    pub fn write_frame(&mut self, frame_buffer: &FrameBuffer) -> Result<()> {
+        let size = SCREEN_WIDTH * SCREEN_HEIGHT;
+        let pixels = frame_buffer.pixels().iter().zip(frame_buffer.layers());
+        for (i, (color, layer)) in pixels.enumerate() {
+            let shades = &self.yuv[match layer {
+                Layer::Background => 0,
+                Layer::Object0 => 1,
+                Layer::Object1 => 2,
+            }];
+            let [y, u, v] = shades[match color {
+                Color::White => 0,
+                Color::LightGray => 1,
+                Color::DarkGray => 2,
+                Color::Black => 3,
+            }];
+            self.planes[i] = y;
+            self.planes[size + i] = u;
+            self.planes[2 * size + i] = v;
+        }
+        self.out.write_all(b"FRAME\n")?;
+        self.out.write_all(&self.planes)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+fn yuv([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let (r, g, b) = (f64::from(r), f64::from(g), f64::from(b));
+    [
+        16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0,
+        128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0,
+        128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0,
+    ]
+    .map(|c| c.round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Recorder;
+    use crate::gb::screen::{FrameBuffer, SCREEN_HEIGHT, SCREEN_WIDTH};
+    use crate::gb::{Color, Layer, Palette, Pixel};
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_y4m_frames() -> anyhow::Result<()> {
+        let out = Shared::default();
+        let mut recorder = Recorder::new(out.clone(), &Palette::default())?;
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.draw(&Pixel {
+            x: 0,
+            y: 0,
+            color: Color::Black,
+            layer: Layer::Background,
+        });
+        recorder.write_frame(&frame_buffer)?;
+        recorder.write_frame(&frame_buffer)?;
+        assert_eq!(recorder.frames(), 2);
+        recorder.finish()?;
+
+        let data = out.0.borrow();
+        let header = b"YUV4MPEG2 W160 H144 F4194304:70224 Ip A1:1 C444\nFRAME\n";
+        assert_eq!(&data[..header.len()], header);
+        let size = SCREEN_WIDTH * SCREEN_HEIGHT;
+        assert_eq!(data.len(), header.len() - 6 + 2 * (6 + 3 * size));
+        // Black, then white, in the Y plane; neutral chroma.
+        assert_eq!(&data[header.len()..header.len() + 2], [16, 235]);
+        assert_eq!(data[header.len() + size], 128);
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use crate::gb::{Color, Layer, Palette, Pixel};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+/// M-cycles from the start of one frame to the next while the LCD is on.
+pub const CYCLES_PER_FRAME: usize = 17556;
+
+/// The last color drawn at every position on the screen.
+#[derive(Clone)]
+pub struct FrameBuffer {
+    pixels: Vec<Color>,
+    layers: Vec<Layer>,
+}
+
+impl FrameBuffer {
+    pub fn new() -> FrameBuffer {
+        FrameBuffer {
+            pixels: vec![Color::White; SCREEN_WIDTH * SCREEN_HEIGHT],
+            layers: vec![Layer::Background; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+
+    /// Draws `pixel`, returning whether it was the last one of the frame.
+    pub fn draw(&mut self, pixel: &Pixel) -> bool {
+        let (x, y) = (usize::from(pixel.x), usize::from(pixel.y));
+        self.pixels[y * SCREEN_WIDTH + x] = pixel.color;
+        self.layers[y * SCREEN_WIDTH + x] = pixel.layer;
+        x == SCREEN_WIDTH - 1 && y == SCREEN_HEIGHT - 1
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * SCREEN_WIDTH + x]
+    }
+
+    /// Row-major, `SCREEN_WIDTH` pixels per row.
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    /// Which palette each pixel was drawn with, in the same order as `pixels`.
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// FNV-1a over every pixel's shade and layer, to compare screens cheaply.
+    pub fn hash(&self) -> u64 {
+        let mut hash = 0xCBF2_9CE4_8422_2325u64;
+        for (color, layer) in self.pixels.iter().zip(&self.layers) {
+            hash ^= (*color as u64) << 2 | *layer as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+        hash
+    }
+
+    /// Packed RGB24 rows, with every pixel repeated `scale` times in both directions.
+    pub fn to_rgb(&self, palette: &Palette, scale: usize) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(self.pixels.len() * 3 * scale * scale);
+        for (row, layers) in self
+            .pixels
+            .chunks(SCREEN_WIDTH)
+            .zip(self.layers.chunks(SCREEN_WIDTH))
+        {
+            let start = rgb.len();
+            for (color, layer) in row.iter().zip(layers) {
+                for _ in 0..scale {
+                    rgb.extend_from_slice(&palette.rgb(*color, *layer));
+                }
+            }
+            for _ in 1..scale {
+                rgb.extend_from_within(start..start + SCREEN_WIDTH * 3 * scale);
+            }
+        }
+        rgb
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> FrameBuffer {
+        FrameBuffer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameBuffer, SCREEN_WIDTH};
+    use crate::gb::{Color, Layer, Palette, Pixel};
+
+    #[test]
+    fn test_scaled_rgb() {
+        let mut frame_buffer = FrameBuffer::new();
+        frame_buffer.draw(&Pixel {
+            x: 1,
+            y: 0,
+            color: Color::Black,
+            layer: Layer::Background,
+        });
+        let rgb = frame_buffer.to_rgb(&Palette::default(), 2);
+        let row = SCREEN_WIDTH * 2 * 3;
+        assert_eq!(rgb.len(), row * 144 * 2);
+        assert_eq!(
+            rgb[..15],
+            [255, 255, 255, 255, 255, 255, 0, 0, 0, 0, 0, 0, 255, 255, 255]
+        );
+        assert_eq!(rgb[..row], rgb[row..row * 2]);
+    }
+}
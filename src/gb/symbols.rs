@@ -0,0 +1,149 @@
+//! RGBDS/no$gmb `.sym` files: one `bank:address label` per line, `;` starts a comment.
+
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+pub struct Symbols {
+    /// Labels at each address, with the bank each belongs to.
+    by_address: BTreeMap<u16, Vec<(u16, String)>>,
+    by_name: HashMap<String, (u16, u16)>,
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        Symbols {
+            by_address: BTreeMap::new(),
+            by_name: HashMap::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Symbols> {
+        Symbols::parse(&fs::read_to_string(path)?).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Symbols> {
+        let mut symbols = Symbols::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+            let malformed = || anyhow!("line {}: malformed symbol {:?}", number + 1, line);
+            let (location, label) = line.split_once(char::is_whitespace).ok_or_else(malformed)?;
+            let (bank, address) = location.split_once(':').ok_or_else(malformed)?;
+            let bank = u16::from_str_radix(bank, 16).map_err(|_| malformed())?;
+            let address = u16::from_str_radix(address, 16).map_err(|_| malformed())?;
+            symbols.insert(bank, address, label.trim());
+        }
+        Ok(symbols)
+    }
+
+    pub fn insert(&mut self, bank: u16, address: u16, label: &str) {
+        self.by_address
+            .entry(address)
+            .or_default()
+            .push((bank, label.to_string()));
+        self.by_name
+            .entry(label.to_string())
+            .or_insert((bank, address));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    /// The `(bank, address)` of a label.
+    pub fn resolve(&self, label: &str) -> Option<(u16, u16)> {
+        self.by_name.get(label).copied()
+    }
+
+    /// The label exactly at `address`, given the ROM bank mapped at 0x4000.
+    pub fn label(&self, address: u16, rom_bank: u16) -> Option<&str> {
+        self.by_address
+            .get(&address)
+            .and_then(|labels| find_in_bank(labels, address, rom_bank))
+    }
+
+    /// The closest preceding label in the same memory region and its offset, e.g. `main+3`.
+    pub fn symbolize(&self, address: u16, rom_bank: u16) -> Option<String> {
+        self.by_address
+            .range(region_start(address)..=address)
+            .rev()
+            .find_map(|(start, labels)| {
+                find_in_bank(labels, address, rom_bank).map(|label| match address - start {
+                    0 => label.to_string(),
+                    offset => format!("{}+{}", label, offset),
+                })
+            })
+    }
+}
+
+impl Default for Symbols {
+    fn default() -> Symbols {
+        Symbols::new()
+    }
+}
+
+fn find_in_bank(labels: &[(u16, String)], address: u16, rom_bank: u16) -> Option<&str> {
+    let bank = match address {
+        0x0000..=0x3FFF => Some(0),
+        0x4000..=0x7FFF => Some(rom_bank),
+        // Only ROM banking is emulated, so labels in other regions match whatever bank they say.
+        _ => None,
+    };
+    labels
+        .iter()
+        .find(|(label_bank, _)| bank.is_none_or(|bank| bank == *label_bank))
+        .map(|(_, label)| label.as_str())
+}
+
+/// Start of the region containing `address`, so that e.g. WRAM addresses never symbolize as an
+/// offset from the last ROM label.
+fn region_start(address: u16) -> u16 {
+    match address {
+        0x0000..=0x3FFF => 0x0000,
+        0x4000..=0x7FFF => 0x4000,
+        0x8000..=0x9FFF => 0x8000,
+        0xA000..=0xBFFF => 0xA000,
+        0xC000..=0xDFFF => 0xC000,
+        0xE000..=0xFF7F => address,
+        0xFF80..=0xFFFF => 0xFF80,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symbols;
+
+    const SYM: &str = "\
+; File generated by rgblink
+00:0150 Main
+00:0158 Main.loop
+01:4000 BankedA
+02:4000 BankedB
+00:C000 wCounter
+";
+
+    #[test]
+    fn test_parse_and_resolve() -> anyhow::Result<()> {
+        let symbols = Symbols::parse(SYM)?;
+        assert_eq!(symbols.resolve("Main.loop"), Some((0, 0x0158)));
+        assert_eq!(symbols.resolve("BankedB"), Some((2, 0x4000)));
+        assert!(Symbols::parse("0150 Main").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_symbolize_uses_mapped_bank() -> anyhow::Result<()> {
+        let symbols = Symbols::parse(SYM)?;
+        assert_eq!(symbols.symbolize(0x0150, 1).as_deref(), Some("Main"));
+        assert_eq!(symbols.symbolize(0x015A, 1).as_deref(), Some("Main.loop+2"));
+        assert_eq!(symbols.symbolize(0x4003, 2).as_deref(), Some("BankedB+3"));
+        assert_eq!(symbols.label(0x4000, 1), Some("BankedA"));
+        assert_eq!(symbols.symbolize(0xC001, 1).as_deref(), Some("wCounter+1"));
+        assert_eq!(symbols.symbolize(0x8000, 1), None);
+        Ok(())
+    }
+}
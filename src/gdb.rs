@@ -0,0 +1,492 @@
+//! A GDB remote serial protocol stub, enabled with `--gdb <port>`.
+//!
+//! GDB has no SM83 target, so registers use its z80 layout: AF, BC, DE, HL, SP and PC as 16-bit
+//! little-endian values, numbered 0 to 5 for `p` and `P`. Until GDB turns acks off, a packet
+//! with a bad checksum is answered with `-` so that GDB sends it again.
+
+use anyhow::{anyhow, Result};
+use gb::{GameBoy, Registers, WatchAction, WatchKind, Watchpoint};
+use itertools::Itertools;
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// How many instructions to run between checks for a ^C from GDB while continuing.
+const INTERRUPT_POLL_INTERVAL: u32 = 4096;
+
+#[derive(PartialEq)]
+enum State {
+    Stopped,
+    Continue,
+    Step,
+}
+
+enum Reply {
+    Send(String),
+    Resume(State),
+    Quit,
+}
+
+/// The connection to GDB, with the bytes that arrived while checking for a ^C kept to be read
+/// first.
+struct Connection {
+    stream: TcpStream,
+    pending: VecDeque<u8>,
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.pending.is_empty() {
+            true => self.stream.read(buf),
+            false => self.pending.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+pub struct GdbStub {
+    stream: Connection,
+    state: State,
+    breakpoints: BTreeSet<u16>,
+    no_ack: bool,
+    until_poll: u32,
+}
+
+impl GdbStub {
+    pub fn listen(port: u16) -> Result<GdbStub> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        println!("Waiting for GDB on {}", listener.local_addr()?);
+        GdbStub::accept(&listener)
+    }
+
+    pub fn accept(listener: &TcpListener) -> Result<GdbStub> {
+        let (stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        Ok(GdbStub {
+            stream: Connection {
+                stream,
+                pending: VecDeque::new(),
+            },
+            state: State::Stopped,
+            breakpoints: BTreeSet::new(),
+            no_ack: false,
+            until_poll: INTERRUPT_POLL_INTERVAL,
+        })
+    }
+
+    /// Called before every `GameBoy::step`. Serves GDB while the target is stopped and returns
+    /// `false` once it kills or detaches.
+    pub fn before_step(&mut self, gb: &mut GameBoy) -> Result<bool> {
+        if self.state != State::Stopped {
+            match self.stop_reason(gb)? {
+                Some(reply) => {
+                    self.state = State::Stopped;
+                    write_packet(&mut self.stream, &reply)?;
+                }
+                None => return Ok(true),
+            }
+        }
+        self.serve(gb)
+    }
+
+    fn stop_reason(&mut self, gb: &mut GameBoy) -> Result<Option<String>> {
+        if let Some(hit) = gb.take_watch_hits().first() {
+            let kind = match hit.watchpoint.kind {
+                WatchKind::Write => "watch",
+                WatchKind::Read => "rwatch",
+                WatchKind::Access => "awatch",
+            };
+            return Ok(Some(format!("T05{}:{:x};", kind, hit.address)));
+        }
+        if self.state == State::Step {
+            return Ok(Some("S05".to_string()));
+        }
+        if self.breakpoints.contains(&gb.registers().pc) {
+            return Ok(Some("T05swbreak:;".to_string()));
+        }
+
+        self.until_poll -= 1;
+        if self.until_poll == 0 {
+            self.until_poll = INTERRUPT_POLL_INTERVAL;
+            if self.poll_interrupt()? {
+                return Ok(Some("S02".to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Checks, without blocking, whether GDB sent a ^C. Anything else it sent is kept for
+    /// `read_packet`.
+    fn poll_interrupt(&mut self) -> Result<bool> {
+        let connection = &mut self.stream;
+        connection.stream.set_nonblocking(true)?;
+        let mut buf = [0u8; 256];
+        let result = loop {
+            match connection.stream.read(&mut buf) {
+                Ok(0) => break Err(anyhow!("GDB disconnected")),
+                Ok(len) => connection.pending.extend(&buf[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(e) => break Err(e.into()),
+            }
+        };
+        connection.stream.set_nonblocking(false)?;
+        result?;
+        Ok(
+            match connection.pending.iter().position(|byte| *byte == 0x03) {
+                Some(index) => {
+                    connection.pending.remove(index);
+                    true
+                }
+                None => false,
+            },
+        )
+    }
+
+    fn serve(&mut self, gb: &mut GameBoy) -> Result<bool> {
+        loop {
+            let packet = match read_packet(&mut self.stream, !self.no_ack)? {
+                Some(packet) => packet,
+                None => return Ok(false),
+            };
+            let reply = self
+                .execute(gb, &packet)
+                .unwrap_or_else(|_| Reply::Send("E01".to_string()));
+            match reply {
+                Reply::Send(reply) => write_packet(&mut self.stream, &reply)?,
+                Reply::Resume(state) => {
+                    self.state = state;
+                    return Ok(true);
+                }
+                Reply::Quit => return Ok(false),
+            }
+        }
+    }
+
+    fn execute(&mut self, gb: &mut GameBoy, packet: &str) -> Result<Reply> {
+        let command = packet.get(..1).unwrap_or_default();
+        let args = packet.get(1..).unwrap_or_default();
+        Ok(Reply::Send(match command {
+            "?" => "S05".to_string(),
+            "g" => registers_to_pairs(&gb.registers())
+                .iter()
+                .map(|pair| hex_le(*pair))
+                .collect(),
+            "G" => {
+                let mut pairs = registers_to_pairs(&gb.registers());
+                for (i, pair) in pairs.iter_mut().enumerate() {
+                    *pair = parse_le(
+                        args.get(i * 4..i * 4 + 4)
+                            .ok_or_else(|| anyhow!("Short G"))?,
+                    )?;
+                }
+                gb.set_registers(pairs_to_registers(&gb.registers(), &pairs));
+                "OK".to_string()
+            }
+            "p" => {
+                let pairs = registers_to_pairs(&gb.registers());
+                match pairs.get(usize::from_str_radix(args, 16)?) {
+                    Some(pair) => hex_le(*pair),
+                    None => "E00".to_string(),
+                }
+            }
+            "P" => {
+                let (n, val) = args.split_once('=').ok_or_else(|| anyhow!("Malformed P"))?;
+                let mut pairs = registers_to_pairs(&gb.registers());
+                *pairs
+                    .get_mut(usize::from_str_radix(n, 16)?)
+                    .ok_or_else(|| anyhow!("No register {}", n))? = parse_le(val)?;
+                gb.set_registers(pairs_to_registers(&gb.registers(), &pairs));
+                "OK".to_string()
+            }
+            "m" => {
+                let (addr, len) = parse_addr_len(args)?;
+                (0..len)
+                    .map(|offset| gb.read_memory(addr.wrapping_add(offset)))
+                    .map_ok(|byte| format!("{:02x}", byte))
+                    .collect::<Result<String>>()?
+            }
+            "M" => {
+                let (addr_len, data) =
+                    args.split_once(':').ok_or_else(|| anyhow!("Malformed M"))?;
+                let (addr, len) = parse_addr_len(addr_len)?;
+                for offset in 0..len {
+                    let i = usize::from(offset) * 2;
+                    let byte = data.get(i..i + 2).ok_or_else(|| anyhow!("Short M"))?;
+                    gb.write_memory(addr.wrapping_add(offset), u8::from_str_radix(byte, 16)?)?;
+                }
+                "OK".to_string()
+            }
+            "c" | "s" => {
+                if !args.is_empty() {
+                    let mut registers = gb.registers();
+                    registers.pc = u16::from_str_radix(args, 16)?;
+                    gb.set_registers(registers);
+                }
+                return Ok(Reply::Resume(if command == "c" {
+                    State::Continue
+                } else {
+                    State::Step
+                }));
+            }
+            "Z" | "z" => self.breakpoint(gb, command == "Z", args)?,
+            "k" => return Ok(Reply::Quit),
+            "D" => {
+                write_packet(&mut self.stream, "OK")?;
+                return Ok(Reply::Quit);
+            }
+            "H" => "OK".to_string(),
+            "q" if args.starts_with("Supported") => {
+                "PacketSize=1000;swbreak+;QStartNoAckMode+".to_string()
+            }
+            "q" if args == "Attached" => "1".to_string(),
+            "Q" if args == "StartNoAckMode" => {
+                self.no_ack = true;
+                "OK".to_string()
+            }
+            _ => String::new(),
+        }))
+    }
+
+    /// Handles `Z`/`z` packets: types 0 and 1 are breakpoints, 2 to 4 write, read and access
+    /// watchpoints over `kind` bytes.
+    fn breakpoint(&mut self, gb: &mut GameBoy, insert: bool, args: &str) -> Result<String> {
+        let (kind, addr, len) = args
+            .split(',')
+            .collect_tuple()
+            .ok_or_else(|| anyhow!("Malformed Z"))?;
+        let addr = u16::from_str_radix(addr, 16)?;
+        let watch_kind = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.breakpoints.insert(addr);
+                } else {
+                    self.breakpoints.remove(&addr);
+                }
+                return Ok("OK".to_string());
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::Access,
+            _ => return Ok(String::new()),
+        };
+        let len = u16::from_str_radix(len, 16)?.max(1);
+        let watchpoint = Watchpoint {
+            start: addr,
+            end: addr.saturating_add(len - 1),
+            kind: watch_kind,
+            value: None,
+            action: WatchAction::Break,
+        };
+        if insert {
+            gb.add_watchpoint(watchpoint);
+        } else {
+            gb.remove_watchpoint(&watchpoint);
+        }
+        Ok("OK".to_string())
+    }
+}
+
+/// Reads the next `$data#cc` packet, skipping acks and stray bytes. With `ack`, it is answered
+/// with `+`, or `-` if it was corrupted and GDB should send it again; without, corrupted packets
+/// are dropped. Returns `None` once the connection is closed.
+fn read_packet(stream: &mut (impl Read + Write), ack: bool) -> Result<Option<String>> {
+    let mut byte = [0u8];
+    loop {
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+        let mut data = vec![];
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'#' {
+                break;
+            }
+            data.push(byte[0]);
+        }
+        let mut checksum = [0u8; 2];
+        stream.read_exact(&mut checksum)?;
+        let expected = std::str::from_utf8(&checksum)
+            .ok()
+            .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+        let actual = data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        if expected == Some(actual) {
+            if ack {
+                stream.write_all(b"+")?;
+            }
+            return Ok(Some(String::from_utf8(unescape(&data))?));
+        }
+        if ack {
+            stream.write_all(b"-")?;
+        }
+    }
+}
+
+/// Undoes `}` escapes, which stand for the next byte XOR 0x20.
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut bytes = data.iter();
+    let mut unescaped = Vec::with_capacity(data.len());
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'}' => unescaped.extend(bytes.next().map(|byte| byte ^ 0x20)),
+            _ => unescaped.push(*byte),
+        }
+    }
+    unescaped
+}
+
+fn write_packet(stream: &mut impl Write, data: &str) -> Result<()> {
+    let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    write!(stream, "${}#{:02x}", data, checksum)?;
+    Ok(())
+}
+
+fn registers_to_pairs(registers: &Registers) -> [u16; 6] {
+    let pair = |hi: u8, lo: u8| u16::from_be_bytes([hi, lo]);
+    [
+        pair(registers.a, registers.f),
+        pair(registers.b, registers.c),
+        pair(registers.d, registers.e),
+        pair(registers.h, registers.l),
+        registers.sp,
+        registers.pc,
+    ]
+}
+
+fn pairs_to_registers(registers: &Registers, pairs: &[u16; 6]) -> Registers {
+    let [a, f] = pairs[0].to_be_bytes();
+    let [b, c] = pairs[1].to_be_bytes();
+    let [d, e] = pairs[2].to_be_bytes();
+    let [h, l] = pairs[3].to_be_bytes();
+    Registers {
+        a,
+        f,
+        b,
+        c,
+        d,
+        e,
+        h,
+        l,
+        sp: pairs[4],
+        pc: pairs[5],
+        ime: registers.ime,
+    }
+}
+
+fn hex_le(val: u16) -> String {
+    val.to_le_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn parse_le(s: &str) -> Result<u16> {
+    let val = u16::from_str_radix(s, 16)?;
+    Ok(val.swap_bytes())
+}
+
+fn parse_addr_len(s: &str) -> Result<(u16, u16)> {
+    let (addr, len) = s
+        .split_once(',')
+        .ok_or_else(|| anyhow!("Malformed address"))?;
+    Ok((
+        u16::from_str_radix(addr, 16)?,
+        u16::from_str_radix(len, 16)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_packet, write_packet, GdbStub};
+    use anyhow::Result;
+    use gb::GameBoy;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    fn exchange(stream: &mut TcpStream, packet: &str) -> Result<String> {
+        write_packet(stream, packet)?;
+        Ok(read_packet(stream, false)?.unwrap_or_default())
+    }
+
+    #[test]
+    fn test_scripted_session() -> Result<()> {
+        let dir = TempDir::new("boyohboy")?;
+        let path = dir.path().join("gdb.gb");
+        let mut rom = vec![0u8; 0x8000];
+        // ld a, $42; ld [$C000], a; nop; jr $0106
+        rom[0x0100..0x0108].copy_from_slice(&[0x3E, 0x42, 0xEA, 0x00, 0xC0, 0x00, 0x18, 0xFE]);
+        fs::write(&path, rom)?;
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let server = thread::spawn(move || -> Result<()> {
+            let mut gb = GameBoy::new(&path)?;
+            let mut stub = GdbStub::accept(&listener)?;
+            while stub.before_step(&mut gb)? {
+                gb.step()?;
+            }
+            Ok(())
+        });
+
+        let mut client = TcpStream::connect(addr)?;
+        client.set_read_timeout(Some(Duration::from_secs(10)))?;
+        assert_eq!(exchange(&mut client, "?")?, "S05");
+        assert_eq!(exchange(&mut client, "p5")?, "0001");
+
+        assert_eq!(exchange(&mut client, "Z0,102,1")?, "OK");
+        assert_eq!(exchange(&mut client, "c")?, "T05swbreak:;");
+        assert_eq!(exchange(&mut client, "p5")?, "0201");
+        assert_eq!(exchange(&mut client, "g")?[2..4], *"42");
+
+        assert_eq!(exchange(&mut client, "Z2,c000,1")?, "OK");
+        assert_eq!(exchange(&mut client, "s")?, "T05watch:c000;");
+        assert_eq!(exchange(&mut client, "mc000,1")?, "42");
+        assert_eq!(exchange(&mut client, "Mc000,2:99aa")?, "OK");
+        assert_eq!(exchange(&mut client, "mc000,2")?, "99aa");
+
+        assert_eq!(exchange(&mut client, "P0=00ff")?, "OK");
+        assert_eq!(exchange(&mut client, "p0")?, "00ff");
+        assert_eq!(exchange(&mut client, "s")?, "S05");
+        assert_eq!(exchange(&mut client, "p5")?, "0601");
+
+        // A corrupted packet is asked for again, and escaped bytes are undone.
+        client.write_all(b"$p5#00")?;
+        let mut nak = [0u8];
+        client.read_exact(&mut nak)?;
+        assert_eq!(nak, *b"-");
+        client.write_all(b"$p}\x15#02")?;
+        assert_eq!(read_packet(&mut client, false)?.unwrap_or_default(), "0601");
+
+        // A packet that arrives while running is served once stopped, not dropped.
+        write_packet(&mut client, "c")?;
+        let mut bytes = vec![];
+        write_packet(&mut bytes, "p5")?;
+        bytes.push(0x03);
+        client.write_all(&bytes)?;
+        assert_eq!(read_packet(&mut client, false)?.unwrap_or_default(), "S02");
+        assert_eq!(read_packet(&mut client, false)?.unwrap_or_default(), "0601");
+
+        write_packet(&mut client, "k")?;
+        server.join().unwrap()?;
+        Ok(())
+    }
+}
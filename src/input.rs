@@ -0,0 +1,316 @@
+//! Keyboard and game controller input, mapped onto the Game Boy's buttons.
+
+use anyhow::{anyhow, Result};
+use gb::Button;
+use log::warn;
+use sdl2::controller::{Axis, Button as PadButton, GameController};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::GameControllerSubsystem;
+use std::fs;
+use std::path::Path;
+
+/// How far a stick has to be pushed to press a direction, out of 32767.
+const STICK_THRESHOLD: i16 = 16384;
+
+/// Turbo buttons are pressed for this many frames, then released for as many.
+const TURBO_FRAMES: u32 = 2;
+
+/// Keys the frontend keeps for its hotkeys, which can't be bound to buttons. Shift changes what
+/// some of them do.
+const HOTKEYS: [Keycode; 21] = [
+    Keycode::LShift,
+    Keycode::RShift,
+    Keycode::Escape,
+    Keycode::P,
+    Keycode::N,
+    Keycode::Tab,
+    Keycode::Backquote,
+    Keycode::F3,
+    Keycode::F4,
+    Keycode::F5,
+    Keycode::F6,
+    Keycode::F7,
+    Keycode::F8,
+    Keycode::F9,
+    Keycode::F10,
+    Keycode::F11,
+    Keycode::F12,
+    Keycode::Equals,
+    Keycode::Minus,
+    Keycode::KpPlus,
+    Keycode::KpMinus,
+];
+
+/// What a key does: hold a button, or press it repeatedly while held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Button(Button),
+    Turbo(Button),
+}
+
+impl Binding {
+    /// `a`, `start`, `turbo_a`...
+    fn by_name(name: &str) -> Option<Binding> {
+        match name.strip_prefix("turbo_") {
+            Some(button) => Button::by_name(button)
+                .filter(|button| matches!(button, Button::A | Button::B))
+                .map(Binding::Turbo),
+            None => Button::by_name(name).map(Binding::Button),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    keys: Vec<(Keycode, Binding)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        Bindings {
+            keys: vec![
+                (Keycode::Right, Binding::Button(Button::Right)),
+                (Keycode::Left, Binding::Button(Button::Left)),
+                (Keycode::Up, Binding::Button(Button::Up)),
+                (Keycode::Down, Binding::Button(Button::Down)),
+                (Keycode::X, Binding::Button(Button::A)),
+                (Keycode::Z, Binding::Button(Button::B)),
+                (Keycode::Backspace, Binding::Button(Button::Select)),
+                (Keycode::Return, Binding::Button(Button::Start)),
+                (Keycode::S, Binding::Turbo(Button::A)),
+                (Keycode::A, Binding::Turbo(Button::B)),
+            ],
+        }
+    }
+}
+
+impl Bindings {
+    pub fn load(path: &Path) -> Result<Bindings> {
+        let text = fs::read_to_string(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Bindings::parse(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// Lines of `button = key, key...` with SDL key names, e.g. `start = Return` or
+    /// `turbo_a = S`. Buttons that aren't mentioned keep their default keys, and hotkeys can't be
+    /// bound.
+    pub fn parse(text: &str) -> Result<Bindings> {
+        let mut bindings = Bindings::default();
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: &str| anyhow!("line {}: {}", number + 1, message);
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (name, keys) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected button = keys"))?;
+            let binding = Binding::by_name(name.trim())
+                .ok_or_else(|| error(&format!("unknown button {}", name.trim())))?;
+            let keys = keys
+                .split(',')
+                .map(|key| {
+                    let keycode = Keycode::from_name(key.trim())
+                        .ok_or_else(|| error(&format!("unknown key {}", key.trim())))?;
+                    if HOTKEYS.contains(&keycode) {
+                        return Err(error(&format!("{} is a hotkey", key.trim())));
+                    }
+                    Ok(keycode)
+                })
+                .collect::<Result<Vec<Keycode>>>()?;
+            bindings.keys.retain(|(_, b)| *b != binding);
+            bindings
+                .keys
+                .extend(keys.into_iter().map(|key| (key, binding)));
+        }
+        Ok(bindings)
+    }
+
+    fn get(&self, keycode: Keycode) -> Option<Binding> {
+        self.keys
+            .iter()
+            .find(|(key, _)| *key == keycode)
+            .map(|(_, binding)| *binding)
+    }
+}
+
+/// D-pad and Start/Back as labelled, and the face buttons by position like on a Game Boy: the
+/// right one is A and the bottom one B, with the top and left ones their turbo versions.
+fn pad_binding(button: PadButton) -> Option<Binding> {
+    match button {
+        PadButton::DPadRight => Some(Binding::Button(Button::Right)),
+        PadButton::DPadLeft => Some(Binding::Button(Button::Left)),
+        PadButton::DPadUp => Some(Binding::Button(Button::Up)),
+        PadButton::DPadDown => Some(Binding::Button(Button::Down)),
+        PadButton::B => Some(Binding::Button(Button::A)),
+        PadButton::A => Some(Binding::Button(Button::B)),
+        PadButton::Back => Some(Binding::Button(Button::Select)),
+        PadButton::Start => Some(Binding::Button(Button::Start)),
+        PadButton::Y => Some(Binding::Turbo(Button::A)),
+        PadButton::X => Some(Binding::Turbo(Button::B)),
+        _ => None,
+    }
+}
+
+/// Held buttons from every source, as `Button::mask` bits.
+#[derive(Default)]
+struct Held {
+    buttons: u8,
+    turbo: u8,
+}
+
+impl Held {
+    fn set(&mut self, binding: Binding, pressed: bool) {
+        let (mask, button) = match binding {
+            Binding::Button(button) => (&mut self.buttons, button),
+            Binding::Turbo(button) => (&mut self.turbo, button),
+        };
+        match pressed {
+            true => *mask |= button.mask(),
+            false => *mask &= !button.mask(),
+        }
+    }
+}
+
+pub struct Input {
+    bindings: Bindings,
+    subsystem: GameControllerSubsystem,
+    /// Open until unplugged.
+    controllers: Vec<GameController>,
+    keyboard: Held,
+    pad: Held,
+    /// D-pad bits from the left stick.
+    stick: u8,
+    frame: u32,
+}
+
+impl Input {
+    pub fn new(bindings: Bindings, subsystem: GameControllerSubsystem) -> Input {
+        Input {
+            bindings,
+            subsystem,
+            controllers: vec![],
+            keyboard: Held::default(),
+            pad: Held::default(),
+            stick: 0,
+            frame: 0,
+        }
+    }
+
+    /// Returns whether `event` was game input, so that it isn't also taken as a hotkey.
+    /// Controllers are opened as they are plugged in, including those present at startup.
+    pub fn handle_event(&mut self, event: &Event) -> Result<bool> {
+        match *event {
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match self.bindings.get(keycode) {
+                Some(binding) => self.keyboard.set(binding, true),
+                None => return Ok(false),
+            },
+            Event::KeyUp {
+                keycode: Some(keycode),
+                ..
+            } => match self.bindings.get(keycode) {
+                Some(binding) => self.keyboard.set(binding, false),
+                None => return Ok(false),
+            },
+            Event::ControllerButtonDown { button, .. } => {
+                if let Some(binding) = pad_binding(button) {
+                    self.pad.set(binding, true);
+                }
+            }
+            Event::ControllerButtonUp { button, .. } => {
+                if let Some(binding) = pad_binding(button) {
+                    self.pad.set(binding, false);
+                }
+            }
+            Event::ControllerAxisMotion { axis, value, .. } => {
+                let (negative, positive) = match axis {
+                    Axis::LeftX => (Button::Left, Button::Right),
+                    Axis::LeftY => (Button::Up, Button::Down),
+                    _ => return Ok(true),
+                };
+                self.stick &= !(negative.mask() | positive.mask());
+                if value <= -STICK_THRESHOLD {
+                    self.stick |= negative.mask();
+                } else if value >= STICK_THRESHOLD {
+                    self.stick |= positive.mask();
+                }
+            }
+            Event::ControllerDeviceAdded { which, .. } => {
+                // A controller that can't be opened is left out rather than ending the session.
+                match self.subsystem.open(which) {
+                    Ok(controller) => {
+                        println!("Controller {} connected", controller.name());
+                        self.controllers.push(controller);
+                    }
+                    Err(e) => warn!("Couldn't open controller {}: {}", which, e),
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.controllers
+                    .retain(|controller| controller.instance_id() != which);
+                // Whatever the controller held when it went away stays released.
+                self.pad = Held::default();
+                self.stick = 0;
+                println!("Controller disconnected");
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// The buttons to press for the next frame.
+    pub fn next_frame(&mut self) -> u8 {
+        self.frame = self.frame.wrapping_add(1);
+        let held = self.keyboard.buttons | self.pad.buttons | self.stick;
+        let turbo = self.keyboard.turbo | self.pad.turbo;
+        match (self.frame / TURBO_FRAMES) % 2 {
+            0 => held | turbo,
+            _ => held,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Binding, Bindings, HOTKEYS};
+    use gb::Button;
+    use sdl2::keyboard::Keycode;
+
+    #[test]
+    fn test_parse_bindings() -> anyhow::Result<()> {
+        let bindings = Bindings::parse("a = K, Space ; jump\n\nturbo_b = Left Ctrl\n")?;
+        assert_eq!(bindings.get(Keycode::K), Some(Binding::Button(Button::A)));
+        assert_eq!(
+            bindings.get(Keycode::Space),
+            Some(Binding::Button(Button::A))
+        );
+        assert_eq!(bindings.get(Keycode::X), None);
+        assert_eq!(
+            bindings.get(Keycode::LCtrl),
+            Some(Binding::Turbo(Button::B))
+        );
+        assert_eq!(bindings.get(Keycode::A), None);
+        assert_eq!(
+            bindings.get(Keycode::Return),
+            Some(Binding::Button(Button::Start))
+        );
+
+        assert!(Bindings::parse("jump = Space").is_err());
+        assert!(Bindings::parse("turbo_start = T").is_err());
+        assert!(Bindings::parse("a = NoSuchKey").is_err());
+        assert!(Bindings::parse("a").is_err());
+        assert!(Bindings::parse("a = Escape").is_err());
+        assert!(Bindings::parse("select = Right Shift").is_err());
+        assert!(Bindings::default()
+            .keys
+            .iter()
+            .all(|(key, _)| !HOTKEYS.contains(key)));
+        assert!(Bindings::parse("start = Tab").is_err());
+        assert!(Bindings::parse("b = K, F12").is_err());
+        assert!(Bindings::parse("turbo_a = Keypad +").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+mod gb;
+mod test;
+
+pub use crate::gb::{
+    Branch, Bus, Button, Cheat, CheatCode, CodeDataLog, Color, Cpu, DebugEvent, Disassembly, Fetch,
+    Filter, FlatRam, Frame, FrameBuffer, FrameKind, GameBoy, Image, InstructionResult,
+    InterruptResult, Layer, Movie, MovieFrame, Palette, Pixel, Profiler, Recorder, Registers,
+    Shades, Symbols, WatchAction, WatchHit, WatchKind, Watchpoint, BUTTONS, CDL_DATA, CDL_OPCODE,
+    CDL_OPERAND, CYCLES_PER_FRAME, FILTERS, PALETTE_PRESETS, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
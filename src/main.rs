@@ -1,103 +1,246 @@
-mod gb;
-mod test;
+use anyhow::{anyhow, Result};
+mod blend;
+mod cli;
+mod debugger;
+mod gdb;
+mod input;
+mod osd;
+mod pacing;
+mod presenter;
 
-use crate::gb::GameBoy;
-
-use crate::gb::Color::{Black, DarkGray, LightGray, White};
-use anyhow::Result;
-use log::{info, LevelFilter};
+use crate::blend::FrameBlender;
+use crate::cli::{parse_args, Options, USAGE};
+use crate::debugger::Debugger;
+use crate::gdb::GdbStub;
+use crate::input::Input;
+use crate::osd::draw_text;
+use crate::pacing::{FpsCounter, Pacer, CYCLES_PER_SECOND};
+use crate::presenter::Presenter;
+use gb::{
+    Cheat, DebugEvent, Filter, GameBoy, Image, Movie, Palette, WatchAction, CYCLES_PER_FRAME,
+    FILTERS, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+use log::warn;
 use log4rs::append::console::ConsoleAppender;
 use log4rs::config::{Appender, Root};
 use log4rs::Config;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::{Color, PixelFormatEnum};
-use std::path::Path;
-use std::time::Instant;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Texture, TextureCreator};
+use sdl2::video::WindowContext;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+/// M-cycles per scanline.
+const CYCLES_PER_LINE: usize = 114;
+const FRAME_DURATION: Duration =
+    Duration::from_nanos((CYCLES_PER_FRAME as f64 / CYCLES_PER_SECOND * 1_000_000_000.0) as u64);
 
 fn main() -> Result<()> {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("gb: {}\n\n{}", e, USAGE);
+            exit(2)
+        }
+    };
+
     let stdout = ConsoleAppender::builder().build();
 
     let config = Config::builder()
         .appender(Appender::builder().build("stdout", Box::new(stdout)))
-        .build(Root::builder().appender("stdout").build(LevelFilter::Warn))
+        .build(Root::builder().appender("stdout").build(options.log_level))
         .unwrap();
     log4rs::init_config(config)?;
 
-    let mut gb = GameBoy::new(Path::new("/Users/jonathan/Downloads/dmg-acid2.gb"))?;
+    if let Err(e) = run(&options) {
+        eprintln!("gb: {}", e);
+        exit(1)
+    }
+    Ok(())
+}
+
+fn run(options: &Options) -> Result<()> {
+    let mut gb = GameBoy::new(&options.rom)?;
+    if let Some(boot_rom) = &options.boot_rom {
+        gb.load_boot_rom(boot_rom)?;
+    }
+    let save_path = save_path(options);
+    if gb.has_battery() && save_path.exists() {
+        gb.load_external_ram(&fs::read(&save_path)?)?;
+    }
+    gb.set_strict(options.strict);
+    let cheats_path = options
+        .cheats
+        .clone()
+        .unwrap_or_else(|| options.rom.with_extension("cht"));
+    if options.cheats.is_some() || cheats_path.exists() {
+        for cheat in Cheat::load(&cheats_path)? {
+            gb.add_cheat(cheat)?;
+        }
+        println!("Loaded {} cheats", gb.cheats().len());
+    }
+    if let Some(path) = &options.play_movie {
+        gb.play_movie(Movie::load(path)?, options.verify_movie)?;
+    }
+    if options.record_movie.is_some() {
+        gb.start_movie_recording()?;
+    }
+    if let Some(trace_path) = &options.trace {
+        gb.set_trace(Some(Box::new(BufWriter::new(File::create(trace_path)?))));
+        gb.set_ly_stub(options.trace_ly_stub);
+        gb.set_trace_symbols(options.trace_symbols);
+    }
+    gb.set_profiling(options.profile.is_some());
+    match &options.cdl {
+        Some(path) if path.exists() => gb.load_code_data_log(path)?,
+        Some(_) => gb.set_code_data_logging(true),
+        None => {}
+    }
+    let mut debugger = if options.debug {
+        Some(Debugger::new())
+    } else {
+        None
+    };
+    let mut gdb = match options.gdb {
+        Some(port) => Some(GdbStub::listen(port)?),
+        None => None,
+    };
+    gb.set_debug_hook(|event| match event {
+        DebugEvent::Lockup { pc, opcode } => {
+            warn!(
+                "CPU locked up on illegal instruction {:#04X} at {:#06X}",
+                opcode, pc
+            )
+        }
+        DebugEvent::Watchpoint(hit) => {
+            if hit.watchpoint.action == WatchAction::Log {
+                warn!("{}", debugger::describe_watch_hit(hit))
+            }
+        }
+    });
+    if options.record {
+        let path = capture_path(options, "y4m");
+        gb.start_recording(&path, &options.palettes[options.palette].1)?;
+        println!("Recording to {}", path.display());
+    }
     let sdl_context = sdl2::init().map_err(anyhow::Error::msg)?;
     let video_subsystem = sdl_context.video().map_err(anyhow::Error::msg)?;
+    let controller_subsystem = sdl_context.game_controller().map_err(anyhow::Error::msg)?;
 
-    let window = video_subsystem
-        .window("boyohboy", 160, 144)
-        .position_centered()
-        .opengl()
-        .build()?;
-
-    let mut canvas = window.into_canvas().build()?;
-    let texture_creator = canvas.texture_creator();
+    let mut window = video_subsystem.window(
+        "boyohboy",
+        SCREEN_WIDTH as u32 * options.scale,
+        SCREEN_HEIGHT as u32 * options.scale,
+    );
+    window.position_centered().resizable().opengl();
+    if options.fullscreen {
+        window.fullscreen_desktop();
+    }
+    let mut presenter = Presenter::new(window.build()?.into_canvas().build()?, options.scaling);
+    let texture_creator = presenter.texture_creator();
     let mut texture = texture_creator
-        .create_texture_streaming(PixelFormatEnum::RGB24, 160, 144)
+        .create_texture_streaming(
+            PixelFormatEnum::RGB24,
+            SCREEN_WIDTH as u32,
+            SCREEN_HEIGHT as u32,
+        )
         .map_err(anyhow::Error::msg)?;
 
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
-    canvas.present();
-    let mut durations: Vec<u128> = vec![];
     let mut event_pump = sdl_context.event_pump().map_err(anyhow::Error::msg)?;
     let mut serial = String::new();
-    let mut colors: Vec<u8> = Vec::with_capacity(160 * 144);
-    let mut frame_start: Option<Instant> = None;
+    let mut frames = gb.frames();
+    let mut frame_cycles = gb.cycles();
+    let mut pacer = Pacer::new(gb.cycles(), Instant::now());
+    let mut fps = FpsCounter::new(gb.cycles(), Instant::now());
+    let mut presented = Instant::now();
+    let mut playing_movie = gb.is_playing_movie();
+    let mut state = State {
+        paused: options.paused,
+        advance: false,
+        fast_forward: false,
+        slow_motion: false,
+        show_fps: options.show_fps,
+        palette: options.palette,
+        filter: options.filter,
+        scanlines: options.scanlines,
+        blend: options.blend.is_some(),
+        blender: FrameBlender::new(options.blend.as_deref().unwrap_or(&[1.0, 1.0])),
+        input: Input::new(options.bindings.clone(), controller_subsystem),
+    };
     'running: loop {
-        let (maybe_log, pixels) = gb.step()?;
+        if state.paused && !state.advance {
+            if let Some(event) = event_pump.wait_event_timeout(100) {
+                if handle_event(event, &mut gb, options, &mut state, &mut presenter)? {
+                    break 'running;
+                }
+                // Show palette, filter and window changes while paused.
+                render(&mut texture, &texture_creator, &state.image(&gb, options))?;
+                presenter.present(&texture)?;
+            }
+            pacer.reset(gb.cycles(), Instant::now());
+            continue;
+        }
+        if let Some(debugger) = debugger.as_mut() {
+            if !debugger.before_step(&mut gb)? {
+                break 'running;
+            }
+        }
+        if let Some(gdb) = gdb.as_mut() {
+            if !gdb.before_step(&mut gb)? {
+                break 'running;
+            }
+        }
+        let (maybe_log, _) = gb.step()?;
         if let Some(log) = maybe_log {
             print!("{}", log);
             serial.push_str(&log);
         }
-        for pixel in pixels.iter() {
-            let intensity: u8 = match &pixel.color {
-                White => 255,
-                LightGray => 2 * (255 / 3),
-                DarkGray => 255 / 3,
-                Black => 0,
-            };
-            colors.push(intensity);
-
-            if pixel.x == 159 && pixel.y == 143 {
-                for event in event_pump.poll_iter() {
-                    match event {
-                        Event::Quit { .. }
-                        | Event::KeyDown {
-                            keycode: Some(Keycode::Escape),
-                            ..
-                        } => break 'running,
-                        _ => {}
-                    }
+
+        // With the LCD off no frames complete, so fall back to a frame's worth of cycles. The
+        // extra scanline keeps the fallback from firing just before a real frame completes.
+        let lcd_off = gb.cycles() - frame_cycles > CYCLES_PER_FRAME + CYCLES_PER_LINE;
+        if gb.frames() != frames || lcd_off {
+            frames = gb.frames();
+            frame_cycles = gb.cycles();
+            state.advance = false;
+            state.blender.push(gb.frame_buffer());
+            for event in event_pump.poll_iter() {
+                if handle_event(event, &mut gb, options, &mut state, &mut presenter)? {
+                    break 'running;
                 }
+            }
+            gb.set_buttons(state.input.next_frame())?;
+            if playing_movie && !gb.is_playing_movie() {
+                playing_movie = false;
+                println!("Movie finished");
+            }
 
-                texture
-                    .with_lock(None, |buffer, _| {
-                        for (i, color) in colors.iter().enumerate() {
-                            buffer[i * 3] = *color;
-                            buffer[i * 3 + 1] = *color;
-                            buffer[i * 3 + 2] = *color;
-                        }
-                    })
-                    .map_err(anyhow::Error::msg)?;
-
-                canvas
-                    .copy(&texture, None, None)
-                    .map_err(anyhow::Error::msg)?;
-                canvas.present();
-                colors.clear();
-
-                if let Some(fs) = frame_start {
-                    durations.push(fs.elapsed().as_nanos());
-                    let fps: f64 = 1_000_000_000f64
-                        / (durations.iter().sum::<u128>() / (durations.len() as u128)) as f64;
-                    info!("{:?} fps", fps);
+            let speed = state.speed(options);
+            pacer.set_speed(speed, gb.cycles(), Instant::now());
+            std::thread::sleep(pacer.delay(gb.cycles(), Instant::now()));
+
+            // Faster than real time, only present as often as the display can show.
+            let now = Instant::now();
+            if speed <= 1.0 || now - presented >= FRAME_DURATION {
+                presented = now;
+                fps.frame(gb.cycles(), now);
+                let mut image = state.image(&gb, options);
+                if state.show_fps {
+                    let text = format!("{:.1} FPS {:.0}%", fps.fps, fps.speed * 100.0);
+                    let scale = image.height / SCREEN_HEIGHT;
+                    draw_text(&mut image, &text, scale);
                 }
-                frame_start = Some(Instant::now());
+                render(&mut texture, &texture_creator, &image)?;
+                presenter.present(&texture)?;
             }
         }
 
@@ -105,5 +248,290 @@ fn main() -> Result<()> {
             break;
         }
     }
+
+    if gb.is_recording() {
+        toggle_recording(&mut gb, options, &state)?;
+    }
+    if let (Some(path), Some(movie)) = (&options.record_movie, gb.stop_movie()) {
+        movie.save(path)?;
+        println!(
+            "Saved {} frame movie to {}",
+            movie.frames.len(),
+            path.display()
+        );
+    }
+    if gb.has_battery() {
+        fs::write(&save_path, gb.external_ram())
+            .map_err(|e| anyhow!("Cannot write save {}: {}", save_path.display(), e))?;
+    }
+    if let (Some(path), Some(profiler)) = (&options.profile, gb.profiler()) {
+        let mut folded = BufWriter::new(File::create(format!("{}.folded", path))?);
+        profiler.write_folded(&mut folded, gb.symbols())?;
+        let mut report = BufWriter::new(File::create(format!("{}.txt", path))?);
+        profiler.write_report(&mut report, gb.symbols())?;
+    }
+    if let (Some(path), Some(code_data_log)) = (&options.cdl, gb.code_data_log()) {
+        code_data_log.save(path)?;
+    }
+    Ok(())
+}
+
+/// `game.sav` in the save directory, or next to `game.gb` by default.
+fn save_path(options: &Options) -> PathBuf {
+    let path = options.rom.with_extension("sav");
+    match (&options.save_dir, path.file_name()) {
+        (Some(dir), Some(name)) => dir.join(name),
+        _ => path,
+    }
+}
+
+/// The first free `game-NNN.<extension>` in the capture directory, or next to `game.gb` by
+/// default.
+fn capture_path(options: &Options, extension: &str) -> PathBuf {
+    let dir = match &options.capture_dir {
+        Some(dir) => dir.as_path(),
+        None => options.rom.parent().unwrap_or(Path::new("")),
+    };
+    let stem = options
+        .rom
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    (1..)
+        .map(|n| dir.join(format!("{}-{:03}.{}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+fn toggle_recording(gb: &mut GameBoy, options: &Options, state: &State) -> Result<()> {
+    match gb.stop_recording()? {
+        Some(frames) => println!("Recorded {} frames", frames),
+        None => {
+            let path = capture_path(options, "y4m");
+            gb.start_recording(&path, state.palette(options))?;
+            println!("Recording to {}", path.display());
+        }
+    }
     Ok(())
 }
+
+/// Frontend settings that hotkeys change while running.
+struct State {
+    paused: bool,
+    /// Index into `Options::palettes`.
+    palette: usize,
+    filter: Filter,
+    scanlines: bool,
+    blend: bool,
+    blender: FrameBlender,
+    /// Run one frame while paused.
+    advance: bool,
+    /// Held down.
+    fast_forward: bool,
+    /// Held down.
+    slow_motion: bool,
+    show_fps: bool,
+    input: Input,
+}
+
+impl State {
+    fn palette<'a>(&self, options: &'a Options) -> &'a Palette {
+        &options.palettes[self.palette].1
+    }
+
+    /// Relative to real time, 0 for as fast as possible.
+    fn speed(&self, options: &Options) -> f64 {
+        match (self.fast_forward, self.slow_motion) {
+            (true, _) => options.fast_forward,
+            (_, true) => options.slow_motion,
+            _ => 1.0,
+        }
+    }
+
+    /// The screen as it is shown in the window, before scaling.
+    fn image(&self, gb: &GameBoy, options: &Options) -> Image {
+        let image = match self.blend {
+            true => self.blender.blend(self.palette(options)),
+            false => Image::new(gb.frame_buffer(), self.palette(options)),
+        };
+        image.post_process(self.filter, self.scanlines)
+    }
+}
+
+fn shift(keymod: Mod) -> bool {
+    keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)
+}
+
+/// Returns whether the user asked to quit.
+fn handle_event(
+    event: Event,
+    gb: &mut GameBoy,
+    options: &Options,
+    state: &mut State,
+    presenter: &mut Presenter,
+) -> Result<bool> {
+    if state.input.handle_event(&event)? {
+        return Ok(false);
+    }
+    match event {
+        Event::Quit { .. }
+        | Event::KeyDown {
+            keycode: Some(Keycode::Escape),
+            ..
+        } => return Ok(true),
+        Event::KeyDown {
+            keycode: Some(Keycode::P),
+            repeat: false,
+            ..
+        } => state.paused = !state.paused,
+        Event::KeyDown {
+            keycode: Some(Keycode::N),
+            ..
+        } if state.paused => state.advance = true,
+        Event::KeyDown {
+            keycode: Some(Keycode::Tab),
+            ..
+        } => state.fast_forward = true,
+        Event::KeyUp {
+            keycode: Some(Keycode::Tab),
+            ..
+        } => state.fast_forward = false,
+        Event::KeyDown {
+            keycode: Some(Keycode::Backquote),
+            ..
+        } => state.slow_motion = true,
+        Event::KeyUp {
+            keycode: Some(Keycode::Backquote),
+            ..
+        } => state.slow_motion = false,
+        Event::KeyDown {
+            keycode: Some(Keycode::F9),
+            repeat: false,
+            ..
+        } => state.show_fps = !state.show_fps,
+        Event::KeyDown {
+            keycode: Some(Keycode::F12),
+            keymod,
+            repeat: false,
+            ..
+        } => {
+            let path = capture_path(options, "png");
+            let saved = if shift(keymod) {
+                // As shown, at least as large as the initial window.
+                let image = state.image(gb, options);
+                let factor = options.scale as usize / (image.width / SCREEN_WIDTH);
+                image.scale(factor.max(1)).save_png(&path)
+            } else {
+                gb.save_screenshot(&path, state.palette(options), 1)
+            };
+            // A failed screenshot shouldn't end the session before the battery is saved.
+            match saved {
+                Ok(()) => println!("Saved {}", path.display()),
+                Err(e) => warn!("Couldn't save screenshot: {}", e),
+            }
+        }
+        Event::KeyDown {
+            keycode: Some(Keycode::F8),
+            repeat: false,
+            ..
+        } if !gb.cheats().is_empty() => {
+            let enabled = !gb.cheats().iter().any(|cheat| cheat.enabled);
+            match (0..gb.cheats().len()).try_for_each(|index| gb.set_cheat_enabled(index, enabled))
+            {
+                Ok(()) => println!("Cheats {}", if enabled { "on" } else { "off" }),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        Event::KeyDown {
+            keycode: Some(Keycode::F10),
+            repeat: false,
+            ..
+        } => {
+            if let Err(e) = toggle_recording(gb, options, state) {
+                warn!("Couldn't toggle recording: {}", e);
+            }
+        }
+        Event::KeyDown {
+            keycode: Some(Keycode::F3),
+            keymod,
+            ..
+        } => {
+            let count = options.palettes.len();
+            state.palette = match shift(keymod) {
+                true => (state.palette + count - 1) % count,
+                false => (state.palette + 1) % count,
+            };
+            println!("Palette {}", options.palettes[state.palette].0);
+        }
+        Event::KeyDown {
+            keycode: Some(Keycode::F5),
+            keymod,
+            ..
+        } => {
+            let count = FILTERS.len();
+            let index = FILTERS
+                .iter()
+                .position(|(_, filter)| *filter == state.filter)
+                .unwrap();
+            state.filter = match shift(keymod) {
+                true => FILTERS[(index + count - 1) % count].1,
+                false => FILTERS[(index + 1) % count].1,
+            };
+            println!("Filter {}", state.filter.name());
+        }
+        Event::KeyDown {
+            keycode: Some(Keycode::F6),
+            repeat: false,
+            ..
+        } => state.scanlines = !state.scanlines,
+        Event::KeyDown {
+            keycode: Some(Keycode::F7),
+            repeat: false,
+            ..
+        } => state.blend = !state.blend,
+        Event::KeyDown {
+            keycode: Some(Keycode::F4),
+            repeat: false,
+            ..
+        } => {
+            presenter.scaling = presenter.scaling.next();
+            println!("Scaling {:?}", presenter.scaling);
+        }
+        Event::KeyDown {
+            keycode: Some(Keycode::F11),
+            repeat: false,
+            ..
+        } => presenter.toggle_fullscreen()?,
+        Event::KeyDown {
+            keycode: Some(Keycode::Equals | Keycode::KpPlus),
+            ..
+        } => presenter.change_scale(1)?,
+        Event::KeyDown {
+            keycode: Some(Keycode::Minus | Keycode::KpMinus),
+            ..
+        } => presenter.change_scale(-1)?,
+        _ => {}
+    }
+    Ok(false)
+}
+
+/// Copies `image` into `texture`, replacing the texture when a filter changes the size.
+fn render<'a>(
+    texture: &mut Texture<'a>,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    image: &Image,
+) -> Result<()> {
+    let query = texture.query();
+    if (query.width as usize, query.height as usize) != (image.width, image.height) {
+        *texture = texture_creator
+            .create_texture_streaming(
+                PixelFormatEnum::RGB24,
+                image.width as u32,
+                image.height as u32,
+            )
+            .map_err(anyhow::Error::msg)?;
+    }
+    texture
+        .update(None, &image.rgb, image.width * 3)
+        .map_err(anyhow::Error::msg)
+}
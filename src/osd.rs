@@ -0,0 +1,99 @@
+//! On-screen text in a 3x5 pixel font, drawn straight into the presented image.
+
+use gb::Image;
+
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+
+/// Rows of three pixels, most significant bit on the left.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b011, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        _ => [0; GLYPH_HEIGHT],
+    }
+}
+
+/// Draws white `text` on a black box at the top left. `scale` is in image pixels per font
+/// pixel, so text keeps its size relative to the screen whatever filter made the image.
+pub fn draw_text(image: &mut Image, text: &str, scale: usize) {
+    let columns = text.chars().count() * (GLYPH_WIDTH + 1) + 1;
+    let rows = GLYPH_HEIGHT + 2;
+    let mut set = |x: usize, y: usize, value: u8| {
+        for py in y * scale..(y + 1) * scale {
+            for px in x * scale..(x + 1) * scale {
+                if px < image.width && py < image.height {
+                    let offset = (py * image.width + px) * 3;
+                    image.rgb[offset..offset + 3].fill(value);
+                }
+            }
+        }
+    };
+    for y in 0..rows {
+        for x in 0..columns {
+            set(x, y, 0);
+        }
+    }
+    for (i, c) in text.chars().enumerate() {
+        for (y, row) in glyph(c).iter().enumerate() {
+            for x in 0..GLYPH_WIDTH {
+                if row & (0b100 >> x) != 0 {
+                    set(1 + i * (GLYPH_WIDTH + 1) + x, 1 + y, 255);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::draw_text;
+    use gb::Image;
+
+    #[test]
+    fn test_draw_text() {
+        let mut image = Image {
+            width: 6,
+            height: 8,
+            rgb: vec![128; 6 * 8 * 3],
+        };
+        draw_text(&mut image, "1", 1);
+        let rows: Vec<String> = image
+            .rgb
+            .chunks(6 * 3)
+            .map(|row| {
+                row.iter()
+                    .step_by(3)
+                    .map(|c| match c {
+                        0 => '.',
+                        255 => '#',
+                        _ => ' ',
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [".....", "..#..", ".##..", "..#..", "..#..", ".###.", ".....", "     "]
+                .map(|row| format!("{} ", row))
+        );
+    }
+}
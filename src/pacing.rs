@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant};
+
+/// M-cycles per second: 70224 T-cycles per frame at 59.73 frames per second.
+pub const CYCLES_PER_SECOND: f64 = 1_048_576.0;
+
+/// How far behind schedule emulation may fall before pacing gives up catching up.
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Keeps emulated time in step with wall clock time at a given speed.
+pub struct Pacer {
+    start: Instant,
+    start_cycles: usize,
+    /// 1.0 for real time, 0.0 for as fast as possible.
+    speed: f64,
+}
+
+impl Pacer {
+    pub fn new(cycles: usize, now: Instant) -> Pacer {
+        Pacer {
+            start: now,
+            start_cycles: cycles,
+            speed: 1.0,
+        }
+    }
+
+    /// Starts pacing afresh from `cycles`, e.g. after a pause.
+    pub fn reset(&mut self, cycles: usize, now: Instant) {
+        self.start = now;
+        self.start_cycles = cycles;
+    }
+
+    pub fn set_speed(&mut self, speed: f64, cycles: usize, now: Instant) {
+        if speed != self.speed {
+            self.speed = speed;
+            self.reset(cycles, now);
+        }
+    }
+
+    /// How long to wait before emulating past `cycles`.
+    pub fn delay(&mut self, cycles: usize, now: Instant) -> Duration {
+        if self.speed == 0.0 {
+            return Duration::ZERO;
+        }
+        let elapsed = (cycles - self.start_cycles) as f64 / (CYCLES_PER_SECOND * self.speed);
+        let target = self.start + Duration::from_secs_f64(elapsed);
+        if target > now {
+            return target - now;
+        }
+        if now - target > MAX_LAG {
+            self.reset(cycles, now);
+        }
+        Duration::ZERO
+    }
+}
+
+/// Presented frames and emulation speed, averaged over about a second.
+pub struct FpsCounter {
+    since: Instant,
+    since_cycles: usize,
+    frames: u32,
+    pub fps: f64,
+    /// Relative to real time.
+    pub speed: f64,
+}
+
+impl FpsCounter {
+    pub fn new(cycles: usize, now: Instant) -> FpsCounter {
+        FpsCounter {
+            since: now,
+            since_cycles: cycles,
+            frames: 0,
+            fps: 0.0,
+            speed: 0.0,
+        }
+    }
+
+    pub fn frame(&mut self, cycles: usize, now: Instant) {
+        self.frames += 1;
+        let elapsed = (now - self.since).as_secs_f64();
+        if elapsed >= 1.0 {
+            self.fps = f64::from(self.frames) / elapsed;
+            self.speed = (cycles - self.since_cycles) as f64 / CYCLES_PER_SECOND / elapsed;
+            *self = FpsCounter {
+                since: now,
+                since_cycles: cycles,
+                frames: 0,
+                ..*self
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FpsCounter, Pacer};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_pacer() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(0, start);
+        // One second of M-cycles is due a second after the start.
+        assert_eq!(
+            pacer.delay(1 << 20, start + Duration::from_millis(250)),
+            Duration::from_millis(750)
+        );
+
+        pacer.set_speed(2.0, 0, start);
+        assert_eq!(pacer.delay(1 << 20, start), Duration::from_millis(500));
+
+        // Far behind schedule, pacing starts over instead of racing to catch up.
+        let late = start + Duration::from_secs(5);
+        assert_eq!(pacer.delay(1 << 20, late), Duration::ZERO);
+        assert_eq!(pacer.delay(3 << 19, late), Duration::from_millis(250));
+
+        pacer.set_speed(0.0, 0, start);
+        assert_eq!(pacer.delay(1 << 30, start), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_fps_counter() {
+        let start = Instant::now();
+        let mut counter = FpsCounter::new(0, start);
+        for frame in 1..=60 {
+            counter.frame(
+                frame * (1 << 20) / 30,
+                start + Duration::from_secs(frame as u64) / 60,
+            );
+        }
+        assert_eq!((counter.fps, counter.speed), (60.0, 2.0));
+    }
+}
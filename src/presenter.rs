@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Result};
+use gb::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::{FullscreenType, WindowContext};
+use std::str::FromStr;
+
+const WIDTH: u32 = SCREEN_WIDTH as u32;
+const HEIGHT: u32 = SCREEN_HEIGHT as u32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scaling {
+    /// The largest whole multiple of the screen that fits, so every pixel is the same size.
+    Integer,
+    /// As large as fits while keeping square pixels.
+    Fit,
+    /// Fills the window, whatever its aspect ratio.
+    Stretch,
+}
+
+impl Scaling {
+    pub fn next(self) -> Scaling {
+        match self {
+            Scaling::Integer => Scaling::Fit,
+            Scaling::Fit => Scaling::Stretch,
+            Scaling::Stretch => Scaling::Integer,
+        }
+    }
+}
+
+impl FromStr for Scaling {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Scaling> {
+        match s {
+            "integer" => Ok(Scaling::Integer),
+            "fit" => Ok(Scaling::Fit),
+            "stretch" => Ok(Scaling::Stretch),
+            _ => Err(anyhow!(
+                "Unknown scaling {}, expected integer, fit or stretch",
+                s
+            )),
+        }
+    }
+}
+
+/// Draws frames into a resizable window, letterboxed according to `Scaling`.
+pub struct Presenter {
+    canvas: WindowCanvas,
+    pub scaling: Scaling,
+}
+
+impl Presenter {
+    pub fn new(canvas: WindowCanvas, scaling: Scaling) -> Presenter {
+        Presenter { canvas, scaling }
+    }
+
+    pub fn texture_creator(&self) -> TextureCreator<WindowContext> {
+        self.canvas.texture_creator()
+    }
+
+    pub fn present(&mut self, texture: &Texture) -> Result<()> {
+        let viewport = viewport(
+            self.scaling,
+            self.canvas.output_size().map_err(anyhow::Error::msg)?,
+        );
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.canvas
+            .copy(texture, None, viewport)
+            .map_err(anyhow::Error::msg)?;
+        self.canvas.present();
+        Ok(())
+    }
+
+    pub fn toggle_fullscreen(&mut self) -> Result<()> {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        window
+            .set_fullscreen(fullscreen)
+            .map_err(anyhow::Error::msg)
+    }
+
+    /// Resizes the window to a whole multiple of the screen. Ignored in fullscreen.
+    pub fn change_scale(&mut self, delta: i32) -> Result<()> {
+        let window = self.canvas.window_mut();
+        if window.fullscreen_state() != FullscreenType::Off {
+            return Ok(());
+        }
+        let (width, height) = window.size();
+        let scale = (width / WIDTH).min(height / HEIGHT) as i32;
+        let scale = (scale + delta).max(1) as u32;
+        window.set_size(WIDTH * scale, HEIGHT * scale)?;
+        Ok(())
+    }
+}
+
+/// Where the screen goes in an output of `width` by `height` pixels.
+pub fn viewport(scaling: Scaling, (width, height): (u32, u32)) -> Rect {
+    let (w, h) = match scaling {
+        Scaling::Integer if width >= WIDTH && height >= HEIGHT => {
+            let scale = (width / WIDTH).min(height / HEIGHT);
+            (WIDTH * scale, HEIGHT * scale)
+        }
+        // Windows smaller than the screen can't be integer scaled, so fit those instead.
+        Scaling::Integer | Scaling::Fit => {
+            if width * HEIGHT < height * WIDTH {
+                (width, width * HEIGHT / WIDTH)
+            } else {
+                (height * WIDTH / HEIGHT, height)
+            }
+        }
+        Scaling::Stretch => (width, height),
+    };
+    Rect::new(
+        ((width - w) / 2) as i32,
+        ((height - h) / 2) as i32,
+        w.max(1),
+        h.max(1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{viewport, Scaling};
+    use sdl2::rect::Rect;
+
+    #[test]
+    fn test_viewport() {
+        assert_eq!(
+            viewport(Scaling::Integer, (1920, 1080)),
+            Rect::new(400, 36, 1120, 1008)
+        );
+        assert_eq!(
+            viewport(Scaling::Fit, (1920, 1080)),
+            Rect::new(360, 0, 1200, 1080)
+        );
+        assert_eq!(
+            viewport(Scaling::Stretch, (1920, 1080)),
+            Rect::new(0, 0, 1920, 1080)
+        );
+        assert_eq!(
+            viewport(Scaling::Integer, (100, 300)),
+            Rect::new(0, 105, 100, 90)
+        );
+    }
+}
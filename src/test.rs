@@ -1,11 +1,20 @@
 #[cfg(test)]
 mod tests {
-    use crate::gb::GameBoy;
+    use crate::gb::{
+        Button, Cheat, DebugEvent, GameBoy, Image, Palette, CDL_DATA, CDL_OPCODE, CDL_OPERAND,
+        CYCLES_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH,
+    };
+    use anyhow::anyhow;
     use log::LevelFilter;
     use log4rs::append::console::ConsoleAppender;
     use log4rs::config::{Appender, Root};
     use log4rs::Config;
-    use std::path::Path;
+    use std::cell::RefCell;
+    use std::fs::{self, File};
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+    use std::time::Instant;
+    use tempdir::TempDir;
 
     #[test]
     fn test_blarrg_01() -> anyhow::Result<()> {
@@ -77,6 +86,623 @@ mod tests {
         run_rom(Path::new("roms/mem_timing.gb"), "mem_timing")
     }
 
+    #[test]
+    fn test_mooneye_acceptance() -> anyhow::Result<()> {
+        run_mooneye_dir(&mooneye_dir())
+    }
+
+    #[test]
+    fn test_mooneye_acceptance_bits() -> anyhow::Result<()> {
+        run_mooneye_dir(&mooneye_dir().join("bits"))
+    }
+
+    #[test]
+    fn test_mooneye_acceptance_instr() -> anyhow::Result<()> {
+        run_mooneye_dir(&mooneye_dir().join("instr"))
+    }
+
+    #[test]
+    fn test_mooneye_acceptance_interrupts() -> anyhow::Result<()> {
+        run_mooneye_dir(&mooneye_dir().join("interrupts"))
+    }
+
+    #[test]
+    fn test_mooneye_acceptance_oam_dma() -> anyhow::Result<()> {
+        run_mooneye_dir(&mooneye_dir().join("oam_dma"))
+    }
+
+    #[test]
+    fn test_mooneye_acceptance_ppu() -> anyhow::Result<()> {
+        run_mooneye_dir(&mooneye_dir().join("ppu"))
+    }
+
+    #[test]
+    fn test_mooneye_acceptance_serial() -> anyhow::Result<()> {
+        run_mooneye_dir(&mooneye_dir().join("serial"))
+    }
+
+    #[test]
+    fn test_mooneye_acceptance_timer() -> anyhow::Result<()> {
+        run_mooneye_dir(&mooneye_dir().join("timer"))
+    }
+
+    /// Emulation speed, for checking changes to the CPU and bus for regressions. Run with
+    /// `cargo test --release bench_cpu_instrs -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_cpu_instrs() -> anyhow::Result<()> {
+        const FRAMES: usize = 3000;
+        let mut gb = GameBoy::new(Path::new("roms/cpu_instrs.gb"))?;
+        let start = Instant::now();
+        while gb.cycles() < FRAMES * CYCLES_PER_FRAME {
+            gb.step()?;
+        }
+        println!(
+            "cpu_instrs: {:.0} frames/s",
+            FRAMES as f64 / start.elapsed().as_secs_f64()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dmg_acid2_screenshot() -> anyhow::Result<()> {
+        let rom = Path::new("roms/dmg-acid2.gb");
+        if !rom.exists() {
+            println!("Skipping dmg-acid2: ROM not found");
+            return Ok(());
+        }
+        let reference = screenshot_path("dmg-acid2");
+        if !reference.exists() && std::env::var_os("UPDATE_SCREENSHOTS").is_none() {
+            return Err(anyhow!(
+                "{} is missing; add the reference image from the dmg-acid2 release",
+                reference.display()
+            ));
+        }
+        check_screenshot(rom, 10, "dmg-acid2")
+    }
+
+    #[test]
+    fn test_tile_screenshot() -> anyhow::Result<()> {
+        // Turn the LCD off, fill tile 0 with the bytes 0x00..0x0F and turn it back on, so that
+        // every BG tile shows the same pattern of all four shades.
+        let (_dir, path) = synthetic_rom(&[
+            0xAF, 0xE0, 0x40, 0x21, 0x00, 0x80, 0x06, 0x10, 0x7D, 0x22, 0x05, 0x20, 0xFB, 0x3E,
+            0x91, 0xE0, 0x40, 0x18, 0xFE,
+        ])?;
+        check_screenshot(&path, 3, "tiles")
+    }
+
+    #[test]
+    fn test_mooneye_harness() -> anyhow::Result<()> {
+        // ld b, 3; ld c, 5; ld d, 8; ld e, 13; ld h, 21; ld l, 34; ld b, b
+        let (_dir, path) = synthetic_rom(&[
+            0x06, 3, 0x0E, 5, 0x16, 8, 0x1E, 13, 0x26, 21, 0x2E, 34, 0x40,
+        ])?;
+        assert_eq!(run_mooneye(&path)?, Mooneye::Passed);
+
+        // ld a, $42; ld b, a; ld c, a; ld d, a; ld e, a; ld h, a; ld l, a; ld b, b
+        let (_dir, path) = synthetic_rom(&[0x3E, 0x42, 0x47, 0x4F, 0x57, 0x5F, 0x67, 0x6F, 0x40])?;
+        assert_eq!(run_mooneye(&path)?, Mooneye::Failed);
+
+        let (_dir, path) = synthetic_rom(&[0x18, 0xFE])?;
+        assert_eq!(run_mooneye(&path)?, Mooneye::TimedOut);
+        Ok(())
+    }
+
+    #[test]
+    fn test_joypad_reads_selected_buttons() -> anyhow::Result<()> {
+        // ld a, $20; ldh ($00), a; jr -2
+        let (_dir, path) = synthetic_rom(&[0x3E, 0x20, 0xE0, 0x00, 0x18, 0xFE])?;
+        let mut gb = GameBoy::new(&path)?;
+        for _ in 0..3 {
+            gb.step()?;
+        }
+        gb.write_memory(0xFF0F, 0)?;
+
+        gb.set_button(Button::A, true)?;
+        assert_eq!(gb.read_memory(0xFF0F)?, 0);
+        gb.set_button(Button::Down, true)?;
+        assert_eq!(gb.read_memory(0xFF00)?, 0xE7);
+        assert_eq!(gb.read_memory(0xFF0F)? & 0x10, 0x10);
+        assert_eq!(gb.buttons(), Button::A.mask() | Button::Down.mask());
+        Ok(())
+    }
+
+    #[test]
+    fn test_movie_replays_exactly() -> anyhow::Result<()> {
+        // ld a, $10; ldh ($00), a; loop: ldh a, ($00); ldh ($47), a; jr loop
+        // Copies the action buttons into BGP, so the screen shows what is held.
+        let (_dir, path) =
+            synthetic_rom(&[0x3E, 0x10, 0xE0, 0x00, 0xF0, 0x00, 0xE0, 0x47, 0x18, 0xFA])?;
+        let mut gb = GameBoy::new(&path)?;
+        gb.start_movie_recording()?;
+        for frame in 0..8 {
+            gb.set_button(Button::A, frame % 3 == 1)?;
+            gb.set_button(Button::Start, frame >= 5)?;
+            while gb.cycles() < (frame + 1) * CYCLES_PER_FRAME {
+                gb.step()?;
+            }
+        }
+        let movie = gb.stop_movie().unwrap();
+        assert_eq!(movie.frames.len(), 8);
+        assert!(movie.frames.iter().any(|frame| frame.buttons != 0));
+
+        let mut replay = GameBoy::new(&path)?;
+        replay.play_movie(movie.clone(), true)?;
+        while replay.is_playing_movie() {
+            replay.set_buttons(0xFF)?;
+            replay.step()?;
+        }
+        assert_eq!(replay.cycles() / CYCLES_PER_FRAME, 8);
+
+        let mut desynced = movie.clone();
+        desynced.frames[6].screen_hash ^= 1;
+        let mut replay = GameBoy::new(&path)?;
+        replay.play_movie(desynced, true)?;
+        let error = loop {
+            if let Err(e) = replay.step() {
+                break e;
+            }
+        };
+        assert!(error.to_string().contains("desynced at frame 6"));
+
+        let mut other = movie.clone();
+        other.rom_crc32 ^= 1;
+        assert!(GameBoy::new(&path)?.play_movie(other, false).is_err());
+
+        // Movies keep the cheats they start with, and play only with the same ones.
+        let mut cheating = GameBoy::new(&path)?;
+        cheating.add_cheat(Cheat::new("0155C0C1", "")?)?;
+        assert!(cheating.play_movie(movie.clone(), false).is_err());
+        cheating.start_movie_recording()?;
+        assert!(cheating.add_cheat(Cheat::new("0166C0C1", "")?).is_err());
+        assert!(cheating.set_cheat_enabled(0, false).is_err());
+        assert!(cheating.remove_cheat(0).is_err());
+        let cheated = cheating.stop_movie().unwrap();
+        assert_eq!(cheated.cheats, ["0155C0C1"]);
+        assert!(GameBoy::new(&path)?.play_movie(cheated, false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cheats() -> anyhow::Result<()> {
+        // loop: ld a, $42; ld ($C000), a; jr loop
+        let (_dir, path) = synthetic_rom(&[0x3E, 0x42, 0xEA, 0x00, 0xC0, 0x18, 0xF9])?;
+        let mut gb = GameBoy::new(&path)?;
+        gb.add_cheat(Cheat::new("991-01F-EA3", "")?)?;
+        // Compares against a byte the ROM doesn't hold, so it never applies.
+        gb.add_cheat(Cheat::new("111-01F-000", "")?)?;
+        gb.add_cheat(Cheat::new("0155C0C1+0066C1A0", "")?)?;
+        for _ in 0..3 {
+            gb.step()?;
+        }
+        assert_eq!(gb.read_memory(0xC000)?, 0x99);
+        assert_eq!(gb.read_memory(0xC1C0)?, 0);
+
+        while gb.cycles() < CYCLES_PER_FRAME {
+            gb.step()?;
+        }
+        assert_eq!(gb.read_memory(0xC1C0)?, 0x55);
+        // Cartridge RAM bank 0 is the only one mapped.
+        assert_eq!(gb.read_memory(0xA0C1)?, 0x66);
+
+        gb.write_memory(0xC1C0, 0)?;
+        gb.set_cheat_enabled(0, false)?;
+        gb.set_cheat_enabled(2, false)?;
+        gb.step_frame()?;
+        assert_eq!(gb.read_memory(0xC000)?, 0x42);
+        assert_eq!(gb.read_memory(0xC1C0)?, 0);
+        assert!(gb.set_cheat_enabled(3, true).is_err());
+        assert_eq!(gb.remove_cheat(1)?.code, "111-01F-000");
+        assert_eq!(gb.cheats().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gameshark_codes_apply_with_lcd_off() -> anyhow::Result<()> {
+        // xor a; ldh ($40), a; loop: jr loop
+        let (_dir, path) = synthetic_rom(&[0xAF, 0xE0, 0x40, 0x18, 0xFE])?;
+        let mut gb = GameBoy::new(&path)?;
+        gb.add_cheat(Cheat::new("0155C0C1", "")?)?;
+        while gb.cycles() < 2 * CYCLES_PER_FRAME {
+            gb.step()?;
+        }
+        assert_eq!(gb.frames(), 0);
+        assert_eq!(gb.read_memory(0xC1C0)?, 0x55);
+
+        gb.write_memory(0xC1C0, 0)?;
+        while gb.cycles() < 3 * CYCLES_PER_FRAME {
+            gb.step()?;
+        }
+        assert_eq!(gb.read_memory(0xC1C0)?, 0x55);
+        Ok(())
+    }
+
+    #[test]
+    fn test_illegal_instruction_locks_up() -> anyhow::Result<()> {
+        let (_dir, path) = synthetic_rom(&[0x00, 0xD3, 0x3C])?;
+        let mut gb = GameBoy::new(&path)?;
+        let events = Rc::new(RefCell::new(vec![]));
+        let hook_events = events.clone();
+        gb.set_debug_hook(move |event| {
+            if let DebugEvent::Lockup { pc, opcode } = event {
+                hook_events.borrow_mut().push((*pc, *opcode))
+            }
+        });
+
+        for _ in 0..100 {
+            gb.step()?;
+        }
+
+        assert_eq!(*events.borrow(), vec![(0x0101, 0xD3)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_illegal_instruction_strict() -> anyhow::Result<()> {
+        let (_dir, path) = synthetic_rom(&[0x00, 0xD3])?;
+        let mut gb = GameBoy::new(&path)?;
+        gb.set_strict(true);
+
+        gb.step()?;
+        assert!(gb.step().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_matches_gameboy_doctor_format() -> anyhow::Result<()> {
+        let (dir, path) = synthetic_rom(&[0x00, 0xF0, 0x44, 0x00])?;
+        let trace_path = dir.path().join("trace.log");
+        let mut gb = GameBoy::new(&path)?;
+        gb.set_trace(Some(Box::new(fs::File::create(&trace_path)?)));
+        gb.set_ly_stub(true);
+
+        for _ in 0..3 {
+            gb.step()?;
+        }
+
+        assert_eq!(
+            fs::read_to_string(&trace_path)?,
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,F0,44,00\n\
+             A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0101 PCMEM:F0,44,00,00\n\
+             A:90 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0103 PCMEM:00,00,00,00\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_symbols_next_to_rom() -> anyhow::Result<()> {
+        // call Func; nop; Func: ret
+        let (dir, path) = synthetic_rom(&[0xCD, 0x04, 0x01, 0x00, 0xC9])?;
+        fs::write(
+            path.with_extension("sym"),
+            "; rgblink\n00:0100 Entry\n00:0104 Func\n",
+        )?;
+        let trace_path = dir.path().join("trace.log");
+        let mut gb = GameBoy::new(&path)?;
+        gb.set_trace(Some(Box::new(fs::File::create(&trace_path)?)));
+        gb.set_trace_symbols(true);
+
+        assert_eq!(gb.disassemble(0x0100)?.text, "call Func");
+        assert_eq!(gb.resolve_symbol("Func"), Some((0, 0x0104)));
+        assert_eq!(gb.symbolize(0x0103).as_deref(), Some("Entry+3"));
+
+        gb.step()?;
+        gb.step()?;
+        let trace = fs::read_to_string(&trace_path)?;
+        assert!(trace.lines().last().unwrap().ends_with(" (Func)"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_data_log() -> anyhow::Result<()> {
+        // ld a, [$0200]; nop
+        let (_dir, path) = synthetic_rom(&[0xFA, 0x00, 0x02, 0x00])?;
+        let mut gb = GameBoy::new(&path)?;
+        gb.set_code_data_logging(true);
+
+        gb.step()?;
+        gb.step()?;
+
+        let flags = gb.code_data_log().unwrap().flags();
+        assert_eq!(flags.len(), 0x8000);
+        assert_eq!(
+            &flags[0x0100..0x0105],
+            &[CDL_OPCODE, CDL_OPERAND, CDL_OPERAND, CDL_OPCODE, 0]
+        );
+        assert_eq!(flags[0x0200], CDL_DATA);
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_data_log_flags_dma_source() -> anyhow::Result<()> {
+        // ld a, $02; ldh [$FF46], a
+        let (_dir, path) = synthetic_rom(&[0x3E, 0x02, 0xE0, 0x46])?;
+        let mut gb = GameBoy::new(&path)?;
+        gb.set_code_data_logging(true);
+
+        gb.step()?;
+        gb.step()?;
+
+        let flags = gb.code_data_log().unwrap().flags();
+        assert!(flags[0x0200..0x02A0].iter().all(|flag| *flag == CDL_DATA));
+        assert_eq!(flags[0x02A0], 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_boot_rom_unmaps_on_write_to_boot() -> anyhow::Result<()> {
+        let (dir, path) = synthetic_rom(&[0x00])?;
+        let boot_rom_path = dir.path().join("boot.bin");
+        let mut boot_rom = vec![0u8; 0x100];
+        // ld a, $01; ldh [$FF50], a
+        boot_rom[..4].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        fs::write(&boot_rom_path, boot_rom)?;
+        let mut gb = GameBoy::new(&path)?;
+        gb.load_boot_rom(&boot_rom_path)?;
+
+        assert_eq!(gb.registers().pc, 0x0000);
+        assert_eq!(gb.read_memory(0x0000)?, 0x3E);
+        gb.step()?;
+        gb.step()?;
+        assert_eq!(gb.read_memory(0x0000)?, 0x00);
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_rom_error() {
+        let error = GameBoy::new(Path::new("/nonexistent/game.gb"))
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "ROM /nonexistent/game.gb not found");
+    }
+
+    #[test]
+    fn test_truncated_rom_error() -> anyhow::Result<()> {
+        let dir = TempDir::new("boyohboy")?;
+        let path = dir.path().join("truncated.gb");
+        // A complete header, but not the rest of the 32 KiB that reads can reach.
+        fs::write(&path, vec![0u8; 0x4000])?;
+        let error = GameBoy::new(&path).err().unwrap();
+        assert!(error.to_string().contains("too small"));
+        Ok(())
+    }
+
+    /// Writes a 32 KiB ROM with `program` at the 0x0100 entry point.
+    fn synthetic_rom(program: &[u8]) -> anyhow::Result<(TempDir, PathBuf)> {
+        let dir = TempDir::new("boyohboy")?;
+        let path = dir.path().join("synthetic.gb");
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x0100..0x0100 + program.len()].copy_from_slice(program);
+        fs::write(&path, rom)?;
+        Ok((dir, path))
+    }
+
+    fn screenshot_path(name: &str) -> PathBuf {
+        Path::new("screenshots").join(format!("{}.png", name))
+    }
+
+    /// Runs `rom` for `frames` frames and compares the screen, rendered with the default palette,
+    /// against `screenshots/<name>.png`. On a mismatch the actual screen and a diff image, with
+    /// differing pixels in red, are written to `target/screenshots`. Set `UPDATE_SCREENSHOTS` to
+    /// rewrite the reference instead.
+    fn check_screenshot(rom: &Path, frames: usize, name: &str) -> anyhow::Result<()> {
+        let mut gb = GameBoy::new(rom)?;
+        while gb.cycles() < frames * CYCLES_PER_FRAME {
+            gb.step()?;
+        }
+        let actual: Vec<[u8; 3]> = gb
+            .frame_buffer()
+            .to_rgb(&Palette::default(), 1)
+            .chunks(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+            .collect();
+
+        let reference = screenshot_path(name);
+        if std::env::var_os("UPDATE_SCREENSHOTS").is_some() {
+            return write_png(&reference, &actual);
+        }
+        let expected = read_png(&reference)?;
+        let mismatches: Vec<usize> = (0..actual.len())
+            .filter(|i| expected[*i] != actual[*i])
+            .collect();
+        if mismatches.is_empty() {
+            return Ok(());
+        }
+
+        let out = Path::new("target/screenshots");
+        fs::create_dir_all(out)?;
+        let diff: Vec<[u8; 3]> = actual
+            .iter()
+            .zip(&expected)
+            .map(|(actual, expected)| match actual == expected {
+                true => actual.map(|c| ((u16::from(c) + 2 * 255) / 3) as u8),
+                false => [255, 0, 0],
+            })
+            .collect();
+        let diff_path = out.join(format!("{}.diff.png", name));
+        write_png(&out.join(format!("{}.actual.png", name)), &actual)?;
+        write_png(&diff_path, &diff)?;
+
+        let xs = || mismatches.iter().map(|i| i % SCREEN_WIDTH);
+        let ys = || mismatches.iter().map(|i| i / SCREEN_WIDTH);
+        let mut report = format!(
+            "{}: {} of {} pixels differ within ({}, {})-({}, {}), diff written to {}",
+            name,
+            mismatches.len(),
+            actual.len(),
+            xs().min().unwrap_or_default(),
+            ys().min().unwrap_or_default(),
+            xs().max().unwrap_or_default(),
+            ys().max().unwrap_or_default(),
+            diff_path.display()
+        );
+        for i in mismatches.iter().take(10) {
+            let [er, eg, eb] = expected[*i];
+            let [ar, ag, ab] = actual[*i];
+            report += &format!(
+                "\n  ({}, {}): expected #{:02X}{:02X}{:02X}, got #{:02X}{:02X}{:02X}",
+                i % SCREEN_WIDTH,
+                i / SCREEN_WIDTH,
+                er,
+                eg,
+                eb,
+                ar,
+                ag,
+                ab
+            );
+        }
+        Err(anyhow!(report))
+    }
+
+    /// Reads a screen-sized PNG of any color type as RGB.
+    fn read_png(path: &Path) -> anyhow::Result<Vec<[u8; 3]>> {
+        let file = File::open(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data)?;
+        if (info.width as usize, info.height as usize) != (SCREEN_WIDTH, SCREEN_HEIGHT) {
+            return Err(anyhow!(
+                "{}: expected {}x{}, got {}x{}",
+                path.display(),
+                SCREEN_WIDTH,
+                SCREEN_HEIGHT,
+                info.width,
+                info.height
+            ));
+        }
+        let channels = info.color_type.samples();
+        Ok(data[..info.buffer_size()]
+            .chunks(channels)
+            .map(|pixel| match pixel {
+                [gray] | [gray, _] => [*gray; 3],
+                [r, g, b, ..] => [*r, *g, *b],
+                _ => unreachable!(),
+            })
+            .collect())
+    }
+
+    fn write_png(path: &Path, pixels: &[[u8; 3]]) -> anyhow::Result<()> {
+        Image {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            rgb: pixels.as_flattened().to_vec(),
+        }
+        .save_png(path)
+    }
+
+    /// Emulated time a mooneye test gets to reach its `LD B,B`, at 2^20 M-cycles per second.
+    const MOONEYE_TIMEOUT_CYCLES: usize = 20 << 20;
+
+    /// Tests, relative to `mooneye_dir()` and without `.gb`, seen failing in a run of the suite.
+    /// They are still run, and reported if they start passing. Fill this from an actual run and
+    /// note the mooneye-test-suite revision it was made with.
+    const MOONEYE_KNOWN_FAILURES: &[&str] = &[];
+
+    #[derive(Debug, PartialEq)]
+    enum Mooneye {
+        Passed,
+        Failed,
+        TimedOut,
+    }
+
+    /// The mooneye-test-suite `acceptance` directory. Override with `MOONEYE_DIR`.
+    fn mooneye_dir() -> PathBuf {
+        std::env::var_os("MOONEYE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("roms/mooneye/acceptance"))
+    }
+
+    /// Runs until the test executes `LD B,B`, then checks for the Fibonacci pass signature in
+    /// B/C/D/E/H/L. Failing tests load 0x42 into all of them instead.
+    fn run_mooneye(path: &Path) -> anyhow::Result<Mooneye> {
+        let mut gb = GameBoy::new(path)?;
+        gb.set_strict(true);
+        while gb.cycles() < MOONEYE_TIMEOUT_CYCLES {
+            let registers = gb.registers();
+            if gb.read_memory(registers.pc)? == 0x40 {
+                let signature = [
+                    registers.b,
+                    registers.c,
+                    registers.d,
+                    registers.e,
+                    registers.h,
+                    registers.l,
+                ];
+                return Ok(if signature == [3, 5, 8, 13, 21, 34] {
+                    Mooneye::Passed
+                } else {
+                    Mooneye::Failed
+                });
+            }
+            gb.step()?;
+        }
+        Ok(Mooneye::TimedOut)
+    }
+
+    /// Whether a mooneye test is meant for DMG-ABC, from the models after the last `-` in its
+    /// name: `-dmgABC...` and `-G...` include it, while e.g. `-S`, `-sgb`, `-mgb`, `-dmg0`, `-A`
+    /// or `-C` don't. Tests without a suffix run on every model.
+    fn is_for_dmg_abc(path: &Path) -> bool {
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        match stem.rsplit_once('-') {
+            Some((_, models)) => models.contains("dmgABC") || models.starts_with('G'),
+            None => true,
+        }
+    }
+
+    /// Runs every DMG-ABC ROM directly inside `dir`, skipping the directory if it isn't there.
+    /// Only failures not in `MOONEYE_KNOWN_FAILURES` fail the test.
+    fn run_mooneye_dir(dir: &Path) -> anyhow::Result<()> {
+        if !dir.is_dir() {
+            println!("Skipping mooneye tests: {} not found", dir.display());
+            return Ok(());
+        }
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<PathBuf>>>()?;
+        paths.sort();
+
+        let mut failures = vec![];
+        for path in paths
+            .iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "gb"))
+            .filter(|path| is_for_dmg_abc(path))
+        {
+            let result = run_mooneye(path).unwrap_or_else(|e| {
+                println!("{}: {}", path.display(), e);
+                Mooneye::Failed
+            });
+            let name = path
+                .strip_prefix(mooneye_dir())
+                .unwrap_or(path)
+                .with_extension("");
+            let known_failure = MOONEYE_KNOWN_FAILURES
+                .iter()
+                .any(|known| Path::new(known) == name);
+            match (result == Mooneye::Passed, known_failure) {
+                (true, true) => println!(
+                    "{}: Passed, remove it from MOONEYE_KNOWN_FAILURES",
+                    path.display()
+                ),
+                (false, true) => println!("{}: {:?} (known failure)", path.display(), result),
+                (passed, false) => {
+                    println!("{}: {:?}", path.display(), result);
+                    if !passed {
+                        failures.push(path.display().to_string());
+                    }
+                }
+            }
+        }
+        assert!(failures.is_empty(), "Failed: {:?}", failures);
+        Ok(())
+    }
+
     fn run_rom(path: &Path, _id: &str) -> anyhow::Result<()> {
         {
             log4rs::init_config(
@@ -90,6 +716,7 @@ mod tests {
             )?;
 
             let mut gb = GameBoy::new(path)?;
+            gb.set_strict(true);
             let mut serial = String::new();
 
             while !serial.contains("Passed") {